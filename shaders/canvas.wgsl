@@ -0,0 +1,28 @@
+// Shows the canvas of the receiver, stretched over the whole window.
+
+@group(0)
+@binding(0)
+var canvas: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A single triangle covering the whole target
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var result: VertexOutput;
+    result.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // The canvas starts at the top
+    result.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return result;
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(canvas));
+    let p = vec2<i32>(vertex.uv * vec2<f32>(size));
+    return textureLoad(canvas, clamp(p, vec2<i32>(0, 0), size - 1), 0);
+}
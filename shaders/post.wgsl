@@ -0,0 +1,180 @@
+// Post processing of captured frames, the scene is rendered `factor` times larger
+// than the output and filtered down here, then the colours are adjusted
+// and the glitch effects applied.
+
+struct Params {
+    // Source pixels per output pixel, in both directions
+    factor: u32,
+    // 0 is a box filter, 1 is Lanczos with a = 2
+    mode: u32,
+    // 0 keeps the shader output, 1 encodes it as sRGB
+    transfer: u32,
+    gamma: f32,
+    brightness: f32,
+    contrast: f32,
+    _pad0: u32,
+    _pad1: u32,
+    // Rows of the calibration matrix
+    matrix: array<vec4<f32>, 3>,
+    // Glitch effects, all off at 0
+    dropout: f32,
+    tearing: f32,
+    shift: f32,
+    noise: f32,
+    blocks: f32,
+    // Changes every frame, so does the glitch pattern
+    seed: u32,
+    _pad2: u32,
+    _pad3: u32,
+};
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the whole target
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn load(p: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(source));
+    return textureLoad(source, clamp(p, vec2<i32>(0, 0), size - 1), 0);
+}
+
+fn sinc(x: f32) -> f32 {
+    if (abs(x) < 0.00001) {
+        return 1.0;
+    }
+    let px = 3.14159265 * x;
+    return sin(px) / px;
+}
+
+fn lanczos(x: f32) -> f32 {
+    if (abs(x) >= 2.0) {
+        return 0.0;
+    }
+    return sinc(x) * sinc(x / 2.0);
+}
+
+fn box_filter(out: vec2<i32>) -> vec4<f32> {
+    let k = i32(params.factor);
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var y = 0; y < k; y = y + 1) {
+        for (var x = 0; x < k; x = x + 1) {
+            sum = sum + load(out * k + vec2<i32>(x, y));
+        }
+    }
+    return sum / f32(k * k);
+}
+
+fn lanczos_filter(out: vec2<i32>) -> vec4<f32> {
+    let k = f32(params.factor);
+    let center = (vec2<f32>(out) + 0.5) * k;
+    let lo = vec2<i32>(floor(center - 2.0 * k));
+    let hi = vec2<i32>(ceil(center + 2.0 * k));
+
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    var total = 0.0;
+    for (var y = lo.y; y < hi.y; y = y + 1) {
+        for (var x = lo.x; x < hi.x; x = x + 1) {
+            // Distance in output pixels
+            let d = (vec2<f32>(f32(x), f32(y)) + 0.5 - center) / k;
+            let w = lanczos(d.x) * lanczos(d.y);
+            sum = sum + load(vec2<i32>(x, y)) * w;
+            total = total + w;
+        }
+    }
+    return clamp(sum / total, vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(1.0, 1.0, 1.0, 1.0));
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    let lo = c * 12.92;
+    let hi = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(hi, lo, c <= vec3<f32>(0.0031308));
+}
+
+fn adjust(c: vec4<f32>) -> vec4<f32> {
+    let zero = vec3<f32>(0.0);
+    let one = vec3<f32>(1.0);
+
+    var rgb = clamp(c.rgb, zero, one);
+    if (params.transfer == 1u) {
+        rgb = srgb_encode(rgb);
+    }
+    rgb = (rgb - 0.5) * params.contrast + 0.5 + params.brightness;
+    rgb = clamp(rgb, zero, one);
+    rgb = vec3<f32>(
+        dot(params.matrix[0].xyz, rgb),
+        dot(params.matrix[1].xyz, rgb),
+        dot(params.matrix[2].xyz, rgb),
+    );
+    rgb = pow(clamp(rgb, zero, one), vec3<f32>(params.gamma));
+    return vec4<f32>(rgb, c.a);
+}
+
+fn downsample(out: vec2<i32>) -> vec4<f32> {
+    if (params.mode == 1u) {
+        return lanczos_filter(out);
+    }
+    return box_filter(out);
+}
+
+// PCG hash, random bits for every pixel, block and row
+fn hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Random number in [0, 1) for `p` and `salt`, different every frame
+fn random(p: vec2<i32>, salt: u32) -> f32 {
+    let h = hash(hash(hash(u32(p.x) ^ (salt << 24u)) + u32(p.y)) + params.seed);
+    return f32(h) / 4294967296.0;
+}
+
+// Where the pixel at `out` is taken from, after tearing and block corruption
+fn displace(out: vec2<i32>) -> vec2<i32> {
+    let size = vec2<i32>(textureDimensions(source)) / i32(params.factor);
+    var p = out;
+
+    // Bands of 4 rows are shifted sideways by up to a fifth of the width
+    let band = vec2<i32>(0, out.y / 4);
+    if (random(band, 1u) < params.tearing) {
+        p.x = p.x + i32((random(band, 2u) - 0.5) * 0.4 * f32(size.x));
+    }
+
+    let block = out / 16;
+    if (random(block, 3u) < params.blocks) {
+        let to = vec2<f32>(random(block, 4u), random(block, 5u)) * vec2<f32>(size);
+        p = vec2<i32>(to) + out % 16;
+    }
+    return p;
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let out = vec2<i32>(floor(position.xy));
+    // Dropped pixels keep what the target held before
+    if (random(out, 6u) < params.dropout) {
+        discard;
+    }
+
+    let p = displace(out);
+    var c = downsample(p);
+    let shift = i32(round(params.shift));
+    if (shift != 0) {
+        c.r = downsample(p + vec2<i32>(shift, 0)).r;
+        c.b = downsample(p - vec2<i32>(shift, 0)).b;
+    }
+
+    c = adjust(c);
+    let noise = vec3<f32>(random(out, 7u), random(out, 8u), random(out, 9u)) - 0.5;
+    return vec4<f32>(clamp(c.rgb + noise * params.noise, vec3<f32>(0.0), vec3<f32>(1.0)), c.a);
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+/// Exponentially growing delays between retries, reset by a success.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(250), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            failures: 0,
+        }
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn fail(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    /// How long to wait after the last failure, doubling with every failure
+    pub fn delay(&self) -> Duration {
+        let doublings = self.failures.saturating_sub(1).min(16);
+        (self.initial * 2u32.pow(doublings)).min(self.max)
+    }
+}
@@ -1,13 +1,99 @@
-use std::{error::Error, time::Instant};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+    time::Instant,
+};
 
 use async_std::fs::read_to_string;
 use clap::{Parser, Subcommand, ValueEnum};
+use futures_util::future::try_join;
 use imager::{
-    francis::{self as francis, Francis, Handler},
-    screenshot::{scrot_new, Ctx},
-    shadertoy::{self as shader_toy, Client, RenderPass},
+    clock::Clock,
+    contact::ContactSheet,
+    export::{AnimFormat, Animation},
+    francis::{self as francis, Handler, Order, Protocol, SinkOptions},
+    froxy::{froxy_configs, load_layout, FroxyConfig, FroxyServer},
+    receiver::{dump_png, Canvas, CanvasView, Receiver},
+    render::{render, PngSequence, RenderOptions},
+    screenshot::{
+        scrot_with, ColourPipeline, Ctx, DownsampleFilter, FrameSettings, Glitch, Transfer,
+    },
+    shadertoy::{
+        self as shader_toy, Client, ClientOptions, ParseMode, PassType, RenderPass, SearchQuery,
+        TimeOffset,
+    },
+    video::{Chroma, VideoFormat, VideoWriter},
 };
 
+/// How to reach the shader toy API
+#[derive(clap::Args, Debug)]
+struct ClientArgs {
+    /// Base url of the shader toy API, for example a local mirror
+    #[arg(long, default_value = "https://www.shadertoy.com")]
+    base_url: String,
+
+    /// Directory to cache downloaded resources in
+    #[arg(long, default_value = "cache")]
+    cache_dir: PathBuf,
+
+    /// Request timeout in seconds
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// How many times failed requests are retried
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Only use cached resources, fail fast when something isn't cached
+    #[arg(long)]
+    offline: bool,
+}
+
+impl ClientArgs {
+    fn client(&self, api: &str) -> Result<Client, Box<dyn Error>> {
+        let options = ClientOptions {
+            base_url: self.base_url.clone(),
+            cache_root: self.cache_dir.clone(),
+            timeout: Duration::from_secs(self.timeout),
+            retries: self.retries,
+            offline: self.offline,
+            ..ClientOptions::default()
+        };
+        Ok(Client::with_options(api, options)?)
+    }
+}
+
+/// Control over shader time, for reproducible output
+#[derive(clap::Args, Debug)]
+struct TimeArgs {
+    /// Seed for the random offset added to shader time, the same seed gives the same offset.
+    /// Offline renders have no offset unless this is set
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Advance time by 1 / fps every frame instead of following the wall clock
+    #[arg(long, value_parser = parse_fps)]
+    fixed_fps: Option<f32>,
+
+    /// Playback speed, 1 is normal speed
+    #[arg(long, default_value_t = 1.0)]
+    rate: f32,
+}
+
+impl TimeArgs {
+    fn clock(&self) -> Clock {
+        let mut clock = match self.fixed_fps {
+            Some(fps) => Clock::fixed(fps),
+            None => Clock::realtime(),
+        };
+        clock.set_rate(self.rate);
+        clock
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Shader {
     Source {
@@ -34,6 +120,106 @@ enum Shader {
         port: u16,
         location: String,
     },
+    /// Render every shader of a playlist at a few times into a contact sheet
+    /// and an HTML index, the thumbnail size is taken from `-x` and `-y`
+    Contact {
+        #[arg(short, long)]
+        api: String,
+
+        /// Shader times of the thumbnails in seconds
+        #[arg(long, value_delimiter = ',', default_value = "1,5,10")]
+        times: Vec<f32>,
+
+        /// Directory to write the sheet, thumbnails and index to
+        #[arg(long, default_value = "contact")]
+        out: PathBuf,
+
+        location: String,
+    },
+    /// List shader toy ids and names matching a search,
+    /// like "top 20 popular with filter multipass"
+    Search {
+        #[arg(short, long)]
+        api: String,
+
+        query: Vec<String>,
+    },
+    /// Check that downloaded shader toy json files parse
+    Validate {
+        /// Require the exact Shadertoy API format instead of parsing leniently
+        #[arg(long)]
+        strict: bool,
+
+        files: Vec<String>,
+    },
+    /// Inspect and manage the resource cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Serve a region layout from a JSON file like the froxy of the wall does,
+    /// to run the server without the wall
+    Froxy {
+        #[arg(short, long, default_value_t = 9100)]
+        port: u16,
+
+        /// JSON list of sections, like `[{"x": 0, "y": 0, "width": 64, "height": 32}]`
+        layout: String,
+    },
+    /// Receive pixels like the wall does, to run the server or francis mode without it.
+    /// The canvas is shown in a window, or with `-m render` only dumped to PNG.
+    /// Its size is taken from `-x` and `-y`, or fits a froxy layout
+    Receive {
+        #[arg(short, long, default_value_t = 9101)]
+        port: u16,
+
+        /// Binary, text, pb or packed, told apart by how connections start when left out
+        #[arg(long)]
+        protocol: Option<Protocol>,
+
+        /// Packed records carry alpha
+        #[arg(long)]
+        alpha: bool,
+
+        /// JSON froxy layout to size the canvas to
+        #[arg(long)]
+        layout: Option<String>,
+
+        /// PNG file to dump the canvas to while it changes
+        #[arg(long)]
+        png: Option<PathBuf>,
+
+        /// Milliseconds between PNG dumps
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// List cached resources
+    List,
+    /// Check cached files against the manifest
+    Verify {
+        /// Remove entries that fail verification
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Remove old entries, then the oldest entries until the cache fits
+    Prune {
+        /// Remove entries fetched more than this many days ago
+        #[arg(long, value_parser = parse_days)]
+        max_age_days: Option<Duration>,
+        /// Remove the oldest entries until the cache is at most this many megabytes
+        #[arg(long)]
+        max_size_mb: Option<f64>,
+    },
+    /// Download everything a playlist needs
+    Prefetch {
+        #[arg(short, long)]
+        api: String,
+        location: String,
+    },
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -41,6 +227,274 @@ enum Mode {
     Window,
     Francis,
     Desktop,
+    /// Render frames to files without a window
+    Render,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum RenderFormat {
+    /// Numbered PNG files in a directory
+    Png,
+    Gif,
+    Apng,
+    Webp,
+    /// Y4M video with 4:2:0 chroma
+    Y4m,
+    /// Y4M video with full resolution chroma
+    Y4m444,
+    /// Raw BGRA frames with a small header
+    Bgra,
+}
+
+impl RenderFormat {
+    fn guess(path: &Path) -> Self {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match ext.to_lowercase().as_str() {
+            "y4m" => RenderFormat::Y4m,
+            "bgra" | "raw" => RenderFormat::Bgra,
+            _ => match AnimFormat::from_path(path) {
+                Some(AnimFormat::Gif) => RenderFormat::Gif,
+                Some(AnimFormat::Apng) => RenderFormat::Apng,
+                Some(AnimFormat::Webp) => RenderFormat::Webp,
+                None => RenderFormat::Png,
+            },
+        }
+    }
+
+    fn animation(self) -> Option<AnimFormat> {
+        match self {
+            RenderFormat::Gif => Some(AnimFormat::Gif),
+            RenderFormat::Apng => Some(AnimFormat::Apng),
+            RenderFormat::Webp => Some(AnimFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn video(self) -> Option<VideoFormat> {
+        match self {
+            RenderFormat::Y4m => Some(VideoFormat::Y4m(Chroma::C420jpeg)),
+            RenderFormat::Y4m444 => Some(VideoFormat::Y4m(Chroma::C444)),
+            RenderFormat::Bgra => Some(VideoFormat::RawBgra),
+            _ => None,
+        }
+    }
+}
+
+/// Options for the render mode, the size is taken from `-x` and `-y`
+#[derive(clap::Args, Debug)]
+struct RenderArgs {
+    /// Directory to write numbered frames to, or the file for other formats.
+    /// Video formats can also go to a named pipe, or to stdout with `-`
+    #[arg(long, default_value = "frames")]
+    out: PathBuf,
+
+    /// Output format, guessed from the extension of `--out` when not set
+    #[arg(long, value_enum)]
+    format: Option<RenderFormat>,
+
+    /// Cross-fade this many seconds of the end into the start, so animations loop seamlessly
+    #[arg(long, default_value_t = 0.0)]
+    loop_fade: f32,
+
+    #[arg(long, default_value_t = 30.0, value_parser = parse_fps)]
+    fps: f32,
+
+    /// Shader time of the first frame in seconds
+    #[arg(long, default_value_t = 0.0)]
+    start: f32,
+
+    /// Seconds to render
+    #[arg(long, default_value_t = 5.0)]
+    duration: f32,
+
+    /// Use a software adapter (lavapipe, llvmpipe), for machines without a GPU
+    #[arg(long)]
+    software: bool,
+
+    /// Pace frames at `--fps` instead of rendering as fast as possible
+    #[arg(long)]
+    realtime: bool,
+
+    /// Render at this multiple of the size and downsample, against aliasing
+    #[arg(long, default_value_t = 1)]
+    supersample: u32,
+
+    /// Filter used to downsample, box or lanczos
+    #[arg(long, default_value = "box")]
+    filter: DownsampleFilter,
+
+    /// Encoding of the shader output, linear keeps it as is, srgb matches the window
+    #[arg(long, default_value = "linear")]
+    transfer: Transfer,
+
+    /// Added to every channel
+    #[arg(long, default_value_t = 0.0)]
+    brightness: f32,
+
+    /// Scales every channel around the middle
+    #[arg(long, default_value_t = 1.0)]
+    contrast: f32,
+
+    /// Exponent applied to every channel last
+    #[arg(long, default_value_t = 1.0)]
+    gamma: f32,
+
+    /// Share of the pixels keeping the previous frame's colour
+    #[arg(long, alias = "failure", default_value_t = 0.0)]
+    dropout: f32,
+
+    /// Share of row bands shifted sideways
+    #[arg(long, default_value_t = 0.0)]
+    tearing: f32,
+
+    /// Pixels the red and blue channels are shifted apart
+    #[arg(long, default_value_t = 0.0)]
+    shift: f32,
+
+    /// Strength of random colour noise
+    #[arg(long, default_value_t = 0.0)]
+    noise: f32,
+
+    /// Share of 16x16 blocks showing another part of the frame
+    #[arg(long, default_value_t = 0.0)]
+    blocks: f32,
+}
+
+impl RenderArgs {
+    fn settings(&self) -> FrameSettings {
+        FrameSettings {
+            supersample: self.supersample,
+            filter: self.filter,
+            colour: ColourPipeline {
+                transfer: self.transfer,
+                brightness: self.brightness,
+                contrast: self.contrast,
+                gamma: self.gamma,
+                ..ColourPipeline::default()
+            },
+            glitch: Glitch {
+                dropout: self.dropout,
+                tearing: self.tearing,
+                shift: self.shift,
+                noise: self.noise,
+                blocks: self.blocks,
+            },
+        }
+    }
+}
+
+/// Where the francis mode draws, the francis address is the positional argument
+#[derive(clap::Args, Debug)]
+struct FrancisModeArgs {
+    /// Froxy to ask for the region to draw in
+    #[arg(long, conflicts_with = "region")]
+    froxy: Option<String>,
+
+    /// Which of the froxy sections to draw in
+    #[arg(long, default_value_t = 0)]
+    section: usize,
+
+    /// Region to draw in as x,y,width,height, instead of asking froxy
+    #[arg(long, value_parser = parse_region)]
+    region: Option<FroxyConfig>,
+
+    /// Binary for francis, text, pb or packed for pixelflut servers
+    #[arg(long, default_value = "binary")]
+    protocol: Protocol,
+
+    /// Send the region's position once with the pixelflut OFFSET command
+    #[arg(long)]
+    offset: bool,
+
+    /// Send the alpha of the frames, so pixelflut servers blend them over the canvas
+    #[arg(long)]
+    alpha: bool,
+
+    /// Split the region over this many concurrent connections
+    #[arg(long, default_value_t = 1)]
+    connections: usize,
+
+    /// Pixels encoded into a single write
+    #[arg(long, default_value_t = 5000)]
+    batch: usize,
+
+    /// Send every pixel of every frame, not just the changed ones
+    #[arg(long)]
+    no_diff: bool,
+
+    /// How far a channel may drift before a pixel is sent again
+    #[arg(long, default_value_t = 0)]
+    threshold: u8,
+
+    /// Milliseconds between full refreshes, repairing pixels others drew over
+    #[arg(long)]
+    refresh: Option<u64>,
+
+    /// Pixel order: random, hilbert, zorder, scanline, importance or dithered
+    #[arg(long, default_value = "random")]
+    order: Order,
+}
+
+fn parse_region(s: &str) -> Result<FroxyConfig, String> {
+    let parts = s
+        .split(',')
+        .map(|p| p.trim().parse::<u16>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid region '{}': {}", s, e))?;
+
+    match parts[..] {
+        [x, y, width, height] => Ok(FroxyConfig {
+            x,
+            y,
+            width,
+            height,
+            port: 0,
+        }),
+        _ => Err(format!("Expected x,y,width,height, got '{}'", s)),
+    }
+}
+
+/// Parses a frame rate, which has to be finite and above zero.
+fn parse_fps(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(fps) if fps.is_finite() && fps > 0.0 => Ok(fps),
+        Ok(_) => Err(format!("Expected a finite frame rate above zero, got '{}'", s)),
+        Err(e) => Err(format!("Invalid frame rate '{}': {}", s, e)),
+    }
+}
+
+/// Parses a non-negative amount of days, fractions allowed.
+fn parse_days(s: &str) -> Result<Duration, String> {
+    let days: f64 = s
+        .parse()
+        .map_err(|e| format!("Invalid amount of days '{}': {}", s, e))?;
+    Duration::try_from_secs_f64(days * 86400.0)
+        .map_err(|e| format!("Invalid amount of days '{}': {}", s, e))
+}
+
+impl FrancisModeArgs {
+    /// The region to draw in, pixelflut servers are asked for their size when it's `None`.
+    async fn region(&self) -> Result<Option<FroxyConfig>, Box<dyn Error>> {
+        if let Some(region) = self.region {
+            return Ok(Some(region));
+        }
+
+        let froxy = match &self.froxy {
+            Some(froxy) => froxy,
+            None if self.protocol != Protocol::Binary => return Ok(None),
+            None => return Err("Francis mode needs --froxy or --region".into()),
+        };
+        let sections = froxy_configs(froxy).await?;
+        match sections.get(self.section) {
+            Some(section) => Ok(Some(*section)),
+            None => Err(format!(
+                "Froxy has {} sections, there is no section {}",
+                sections.len(),
+                self.section
+            )
+            .into()),
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -49,7 +503,7 @@ pub struct FrancisArgs {
     #[arg(value_enum, short, long, default_value_t = Mode::Window)]
     mode: Mode,
 
-    /// Francis location
+    /// Francis or pixelflut location
     addr: Option<String>,
 
     #[arg(short, long)]
@@ -57,6 +511,18 @@ pub struct FrancisArgs {
     #[arg(short, long)]
     y: Option<u16>,
 
+    #[command(flatten)]
+    client: ClientArgs,
+
+    #[command(flatten)]
+    render: RenderArgs,
+
+    #[command(flatten)]
+    time: TimeArgs,
+
+    #[command(flatten)]
+    francis: FrancisModeArgs,
+
     #[command(subcommand)]
     command: Shader,
 }
@@ -68,7 +534,7 @@ fn fuji_args(width: f32, height: f32) -> shader_toy::Args {
         code: include_str!("../../shaders/splash.glsl").to_string(),
         name: "Source Shader".into(),
         description: "".into(),
-        pass_type: "image".into(),
+        pass_type: PassType::Image,
     }];
 
     shader_toy::Args {
@@ -77,24 +543,28 @@ fn fuji_args(width: f32, height: f32) -> shader_toy::Args {
         name: "Splash".into(),
         width,
         height,
+        offset: TimeOffset::Random,
     }
 }
 
 async fn run_francis() -> Result<(), Box<dyn Error>> {
     let args = FrancisArgs::parse();
 
-    println!("Got GPU Ctx");
+    eprintln!("Got GPU Ctx");
 
-    let input = match args.command {
+    let mut input = match args.command {
         Shader::Source { location } => shader_toy::Args::from_source(location, 0., 0.).await?,
         Shader::Local { api, location } => {
-            shader_toy::Args::from_local(&api, location, 0., 0.).await?
+            shader_toy::Args::from_local(&args.client.client(&api)?, location, 0., 0.).await?
         }
         Shader::Toy {
             api,
             shader_id,
             save,
-        } => shader_toy::Args::from_toy(&api, shader_id, save, 0., 0.).await?,
+        } => {
+            let client = args.client.client(&api)?;
+            shader_toy::Args::from_toy(&client, shader_id, save, 0., 0.).await?
+        }
         Shader::Server {
             location,
             api,
@@ -103,14 +573,239 @@ async fn run_francis() -> Result<(), Box<dyn Error>> {
             let config = read_to_string(location).await?;
             let config: francis::Options = serde_json::from_str(&config)?;
 
-            let handler = Handler::new(&api, config, port).await?;
+            let handler = Handler::new(args.client.client(&api)?, config, port).await?;
             handler.start().await?;
 
             return Ok(());
         }
+        Shader::Contact {
+            api,
+            times,
+            out,
+            location,
+        } => {
+            let config = read_to_string(location).await?;
+            let config: francis::Options = serde_json::from_str(&config)?;
+            let client = args.client.client(&api)?;
+
+            let offset = match args.time.seed {
+                Some(seed) => TimeOffset::Seeded(seed),
+                None => TimeOffset::Fixed(0.0),
+            };
+            let ctx = Ctx::request::<shader_toy::Example>(args.render.software).await?;
+
+            let (w, h) = (args.x.unwrap_or(192) as u32, args.y.unwrap_or(108) as u32);
+            let mut sheet = ContactSheet::new(w, h, times);
+            for entry in config.entries(&client).await {
+                eprintln!("Rendering {}", entry.location());
+                sheet.render(&ctx, &client, entry, offset).await;
+            }
+            sheet.save(&out)?;
+
+            let failed = sheet.rows().iter().filter(|r| r.frames.is_err()).count();
+            eprintln!(
+                "Wrote {} shaders to {:?}, {} failed",
+                sheet.rows().len(),
+                out,
+                failed
+            );
+            return Ok(());
+        }
+        Shader::Search { api, query } => {
+            let query: SearchQuery = query.join(" ").parse()?;
+            let client = args.client.client(&api)?;
+
+            for id in query.resolve(&client, false).await? {
+                let shader = client.get_shader(&id, None).await?;
+                println!("{}\t{}", id, shader.info.name);
+            }
+
+            return Ok(());
+        }
+        Shader::Cache { command } => {
+            run_cache(&args.client, command).await?;
+            return Ok(());
+        }
+        Shader::Froxy { port, layout } => {
+            let sections = load_layout(&layout).await?;
+            let server = FroxyServer::bind(("127.0.0.1", port), &sections).await?;
+            println!(
+                "Serving {} sections from {} on {}",
+                sections.len(),
+                layout,
+                server.local_addr()?
+            );
+            server.run().await?;
+            return Ok(());
+        }
+        Shader::Receive {
+            port,
+            protocol,
+            alpha,
+            layout,
+            png,
+            interval,
+        } => {
+            let window = matches!(args.mode, Mode::Window | Mode::Desktop);
+            if !window && png.is_none() {
+                return Err("Receiving without a window needs --png".into());
+            }
+
+            let (width, height) = match layout {
+                Some(layout) => load_layout(&layout).await?.iter().fold((0, 0), |(w, h), s| {
+                    (
+                        w.max(s.x as u32 + s.width as u32),
+                        h.max(s.y as u32 + s.height as u32),
+                    )
+                }),
+                None => (args.x.unwrap_or(500) as u32, args.y.unwrap_or(500) as u32),
+            };
+            let canvas = Canvas::shared(width, height);
+
+            let receiver =
+                Receiver::bind(("127.0.0.1", port), canvas.clone(), protocol, alpha).await?;
+            println!(
+                "Receiving a {}x{} canvas on {}",
+                width,
+                height,
+                receiver.local_addr()?
+            );
+            let receiving = async_std::task::spawn(receiver.run());
+
+            let dumping = png.map(|png| {
+                let interval = Duration::from_millis(interval);
+                async_std::task::spawn(dump_png(canvas.clone(), png, interval))
+            });
+
+            if window {
+                let args = imager::Args {
+                    x_pos: 0,
+                    y_pos: 0,
+                    width,
+                    height,
+                    single: false,
+                    display: match args.mode {
+                        Mode::Desktop => imager::Display::Desktop,
+                        _ => imager::Display::Window,
+                    },
+                };
+                let clock = Clock::realtime();
+                let setup = imager::framework::setup::<CanvasView>(&args).await;
+                imager::framework::start::<CanvasView>(setup, args, canvas, clock).await;
+            } else if let Some(dumping) = dumping {
+                try_join(receiving, dumping).await?;
+            }
+            return Ok(());
+        }
+        Shader::Validate { strict, files } => {
+            let mode = if strict {
+                ParseMode::Strict
+            } else {
+                ParseMode::Lenient
+            };
+
+            let mut failed = 0;
+            for file in &files {
+                let json = read_to_string(file).await?;
+                match shader_toy::Shader::from_json(&json, mode) {
+                    Ok(_) => println!("ok     {}", file),
+                    Err(e) => {
+                        failed += 1;
+                        println!("failed {}: {}", file, e);
+                    }
+                }
+            }
+
+            if failed > 0 {
+                return Err(format!("{} of {} files failed validation", failed, files.len()).into());
+            }
+            return Ok(());
+        }
     };
 
     match args.mode {
+        Mode::Render => {
+            let options = RenderOptions {
+                width: args.x.unwrap_or(500) as u32,
+                height: args.y.unwrap_or(500) as u32,
+                fps: args.render.fps,
+                start: args.render.start,
+                duration: args.render.duration,
+                realtime: args.render.realtime,
+                settings: args.render.settings(),
+            };
+            input.width = options.width as f32;
+            input.height = options.height as f32;
+            input.offset = match args.time.seed {
+                Some(seed) => TimeOffset::Seeded(seed),
+                None => TimeOffset::Fixed(0.0),
+            };
+
+            let format = args
+                .render
+                .format
+                .unwrap_or_else(|| RenderFormat::guess(&args.render.out));
+
+            let ctx = Ctx::request::<shader_toy::Example>(args.render.software).await?;
+
+            let start = Instant::now();
+            match format.animation() {
+                Some(anim_format) => {
+                    let mut anim = Animation::new(options.width, options.height, options.fps);
+                    render::<shader_toy::Example, _>(&ctx, input, &options, |_, frame| {
+                        anim.push(&frame);
+                        Ok(())
+                    })
+                    .await?;
+
+                    anim.seamless((args.render.loop_fade * options.fps).round() as usize);
+                    anim.save(&args.render.out, anim_format)?;
+                }
+                None => match format.video() {
+                    Some(video_format) => {
+                        // `-` streams to stdout, anything else is a file or named pipe
+                        let out: Box<dyn Write> = if args.render.out == Path::new("-") {
+                            Box::new(std::io::stdout().lock())
+                        } else {
+                            Box::new(File::create(&args.render.out)?)
+                        };
+
+                        let mut video = VideoWriter::new(
+                            BufWriter::new(out),
+                            video_format,
+                            options.width,
+                            options.height,
+                            options.fps,
+                        );
+                        render::<shader_toy::Example, _>(&ctx, input, &options, |_, frame| {
+                            video.write(&frame)?;
+                            // Don't keep realtime frames waiting in the buffer
+                            if options.realtime {
+                                video.flush()?;
+                            }
+                            Ok(())
+                        })
+                        .await?;
+                        video.flush()?;
+                    }
+                    None => {
+                        let out = PngSequence::create(&args.render.out)?;
+                        render::<shader_toy::Example, _>(&ctx, input, &options, |i, frame| {
+                            out.write(i, &frame)
+                        })
+                        .await?;
+                    }
+                },
+            }
+
+            eprintln!(
+                "Rendered {} frames to {:?} in {:.1}s",
+                options.frame_count(),
+                args.render.out,
+                start.elapsed().as_secs_f32()
+            );
+            Ok(())
+        }
         Mode::Window | Mode::Desktop => {
             let display = match args.mode {
                 Mode::Window => imager::Display::Window,
@@ -118,6 +813,11 @@ async fn run_francis() -> Result<(), Box<dyn Error>> {
                 _ => unreachable!(),
             };
 
+            if let Some(seed) = args.time.seed {
+                input.offset = TimeOffset::Seeded(seed);
+            }
+            let clock = args.time.clock();
+
             let args = imager::Args {
                 x_pos: 0,
                 y_pos: 0,
@@ -128,47 +828,131 @@ async fn run_francis() -> Result<(), Box<dyn Error>> {
             };
             let setup = imager::framework::setup::<shader_toy::Example>(&args).await;
             imager::framework::screen::<shader_toy::Example>(&setup, fuji_args(args.width as f32, args.height as f32)).await;
-            imager::framework::start::<shader_toy::Example>(setup, args, input).await;
+            imager::framework::start::<shader_toy::Example>(setup, args, input, clock).await;
             Ok(())
         }
         Mode::Francis => {
-            panic!("Francis mode is no longer supported")
-            // let mut francis =
-            //     Francis::new(&args.addr.expect("Please specify addr"), args.x, args.y)
-            //         .await
-            //         .unwrap();
-            // println!("Created francis");
-            //
-            // let ctx = Ctx::new::<shader_toy::Example>().await;
-            //
-            // let mut runner =
-            //     scrot_new::<shader_toy::Example>(&ctx, francis.width(), francis.height(), input)
-            //         .await?;
-            //
-            // let start = Instant::now();
-            // let mut count = 0;
-            // let mut fps = Instant::now();
-            // loop {
-            //     let frame = runner
-            //         .frame(&ctx, start.elapsed().as_secs_f32(), None)
-            //         .await;
-            //     francis.write(frame.buffer, 4).await.unwrap();
-            //
-            //     count += 1;
-            //     if fps.elapsed().as_secs_f32() > 1.0 {
-            //         fps = Instant::now();
-            //         println!("fps {}", count);
-            //         count = 0;
-            //     }
-            // }
+            let addr = args.addr.as_ref().ok_or("Francis mode needs the francis address")?;
+            let region = args.francis.region().await?;
+            let mut francis = args
+                .francis
+                .protocol
+                .connect(
+                    addr,
+                    region,
+                    SinkOptions {
+                        offset: args.francis.offset,
+                        alpha: args.francis.alpha,
+                        connections: args.francis.connections,
+                        batch: args.francis.batch,
+                        diff: !args.francis.no_diff,
+                        threshold: args.francis.threshold,
+                        refresh: args.francis.refresh,
+                        order: args.francis.order,
+                    },
+                )
+                .await?;
+
+            if let Some(seed) = args.time.seed {
+                input.offset = TimeOffset::Seeded(seed);
+            }
+            let mut clock = args.time.clock();
+
+            let ctx = Ctx::request::<shader_toy::Example>(args.render.software).await?;
+            let mut anim = scrot_with::<shader_toy::Example>(
+                &ctx,
+                francis.width(),
+                francis.height(),
+                input,
+                args.render.settings(),
+            )
+            .await?;
+
+            let mut count = 0;
+            let mut fps = Instant::now();
+            loop {
+                // Keep the GPU busy with the next frames while this one is sent
+                while anim.in_flight() < anim.depth() {
+                    anim.submit(&ctx, clock.tick(), None);
+                }
+                let frame = anim.receive(&ctx).await.expect("frames are in flight")?;
+                francis.write(frame.buffer, 4).await?;
+
+                count += 1;
+                if fps.elapsed().as_secs_f32() >= 1.0 {
+                    eprintln!("{:.1} fps", count as f32 / fps.elapsed().as_secs_f32());
+                    fps = Instant::now();
+                    count = 0;
+                }
+            }
+        }
+    }
+}
+
+async fn run_cache(client_args: &ClientArgs, command: CacheCommand) -> Result<(), Box<dyn Error>> {
+    let client = client_args.client("")?;
+    let cache = &client.cache;
+
+    match command {
+        CacheCommand::List => {
+            let entries = cache.entries();
+            for (key, entry) in &entries {
+                println!(
+                    "{:>10} {:>6.1}d {}  {}",
+                    entry.size,
+                    entry.age().as_secs_f64() / 86400.0,
+                    entry.hash.get(..12).unwrap_or(&entry.hash),
+                    key
+                );
+            }
+            let total: u64 = entries.iter().map(|(_, e)| e.size).sum();
+            println!("{} entries, {} bytes in {:?}", entries.len(), total, cache.root());
+        }
+        CacheCommand::Verify { fix } => {
+            let problems = cache.verify().await;
+            for (key, problem) in &problems {
+                println!("{:?} {}", problem, key);
+                if fix {
+                    cache.remove(key).await?;
+                }
+            }
+            if problems.is_empty() {
+                println!("All {} entries are fine", cache.entries().len());
+            } else if !fix {
+                return Err(format!("{} entries failed verification", problems.len()).into());
+            }
+        }
+        CacheCommand::Prune {
+            max_age_days,
+            max_size_mb,
+        } => {
+            let max_size = max_size_mb.map(|mb| (mb * 1024.0 * 1024.0) as u64);
+            let removed = cache.prune(max_age_days, max_size).await?;
+            for key in &removed {
+                println!("removed {}", key);
+            }
+            println!("Removed {} entries", removed.len());
+        }
+        CacheCommand::Prefetch { api, location } => {
+            let client = client_args.client(&api)?;
+            let config = read_to_string(location).await?;
+            let config: francis::Options = serde_json::from_str(&config)?;
+
+            let fetched = config.prefetch(&client).await?;
+            println!("Downloaded {} resources", fetched);
         }
     }
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
     match run_francis().await {
         Ok(_) => {}
-        Err(e) => eprintln!("Error {:?}", e),
+        Err(e) => {
+            eprintln!("Error {:?}", e);
+            std::process::exit(1);
+        }
     };
 }
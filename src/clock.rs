@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+/// Step used by `Clock::step` when the clock follows the wall clock.
+const DEFAULT_STEP: f32 = 1.0 / 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    /// Advance by the wall clock time between ticks
+    Realtime,
+    /// Advance by the same step every tick, whatever the frame rate
+    Fixed { step: f32 },
+}
+
+/// Shader time, decoupled from the wall clock so it can be paused, stepped,
+/// sought and sped up, or advanced with a fixed timestep for reproducible output.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    mode: ClockMode,
+    rate: f32,
+    paused: bool,
+    time: f32,
+    last_tick: Option<Instant>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::realtime()
+    }
+}
+
+impl Clock {
+    pub fn realtime() -> Self {
+        Self {
+            mode: ClockMode::Realtime,
+            rate: 1.0,
+            paused: false,
+            time: 0.0,
+            last_tick: None,
+        }
+    }
+
+    /// A clock advancing `1 / fps` seconds every tick.
+    pub fn fixed(fps: f32) -> Self {
+        Self {
+            mode: ClockMode::Fixed { step: 1.0 / fps },
+            ..Self::realtime()
+        }
+    }
+
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+
+    /// Advances the clock for a new frame and returns the time of that frame.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = match self.mode {
+            ClockMode::Realtime => self
+                .last_tick
+                .map(|last| now.duration_since(last))
+                .unwrap_or(Duration::ZERO)
+                .as_secs_f32(),
+            // The first tick shows time 0
+            ClockMode::Fixed { step } if self.last_tick.is_some() => step,
+            ClockMode::Fixed { .. } => 0.0,
+        };
+        self.last_tick = Some(now);
+
+        if !self.paused {
+            self.time = (self.time + elapsed * self.rate).max(0.0);
+        }
+        self.time
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.0);
+    }
+
+    /// Moves `frames` steps forward, or back when negative. Mostly useful while paused.
+    pub fn step(&mut self, frames: i32) {
+        let step = match self.mode {
+            ClockMode::Fixed { step } => step,
+            ClockMode::Realtime => DEFAULT_STEP,
+        };
+        self.seek(self.time + frames as f32 * step);
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// Playback speed, 1 is normal speed. Negative rates play backwards.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
@@ -0,0 +1,244 @@
+mod font;
+pub use font::*;
+
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::{
+    francis::Entry,
+    screenshot::{scrot_new, Ctx},
+    shadertoy::{Client, Example, TimeOffset},
+};
+
+const PADDING: u32 = 8;
+const TEXT_SCALE: u32 = 2;
+const BACKGROUND: Rgba<u8> = Rgba([24, 24, 24, 255]);
+const TEXT: Rgba<u8> = Rgba([230, 230, 230, 255]);
+const FAILED: Rgba<u8> = Rgba([96, 16, 16, 255]);
+
+/// A playlist entry rendered at every time of the sheet
+pub struct Row {
+    pub entry: Entry,
+    /// Name of the shader, or the location when it didn't load
+    pub name: String,
+    /// A frame per time, or why the shader couldn't be rendered
+    pub frames: Result<Vec<RgbaImage>, String>,
+}
+
+/// Thumbnails of playlist entries at a few times,
+/// a row per entry and a column per time.
+pub struct ContactSheet {
+    width: u32,
+    height: u32,
+    times: Vec<f32>,
+    rows: Vec<Row>,
+}
+
+impl ContactSheet {
+    pub fn new(width: u32, height: u32, times: Vec<f32>) -> Self {
+        Self {
+            width,
+            height,
+            times,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn push(&mut self, row: Row) {
+        self.rows.push(row);
+    }
+
+    /// Renders `entry` at every time of the sheet and adds it as a row.
+    /// Entries that fail to load or build are added with the error.
+    pub async fn render(&mut self, ctx: &Ctx, client: &Client, entry: Entry, offset: TimeOffset) {
+        let (name, frames) = match self.render_frames(ctx, client, &entry, offset).await {
+            Ok((name, frames)) => (name, Ok(frames)),
+            Err(e) => (entry.location().to_string(), Err(strip_ansi(&e.to_string()))),
+        };
+        self.push(Row {
+            entry,
+            name,
+            frames,
+        });
+    }
+
+    async fn render_frames(
+        &self,
+        ctx: &Ctx,
+        client: &Client,
+        entry: &Entry,
+        offset: TimeOffset,
+    ) -> Result<(String, Vec<RgbaImage>), Box<dyn Error>> {
+        let mut args = entry
+            .args(client, self.width as f32, self.height as f32)
+            .await?;
+        args.offset = offset;
+        let name = args.name.clone();
+
+        let mut anim = scrot_new::<Example>(ctx, self.width, self.height, args).await?;
+        let mut frames = Vec::with_capacity(self.times.len());
+        for &time in &self.times {
+            frames.push(anim.frame(ctx, time, None).await?.to_image());
+        }
+        Ok((name, frames))
+    }
+
+    fn label_height() -> u32 {
+        GLYPH_HEIGHT * TEXT_SCALE + PADDING
+    }
+
+    /// Composes the sheet: the times along the top, then every row
+    /// with its name above the thumbnails.
+    pub fn compose(&self) -> RgbaImage {
+        let columns = self.times.len().max(1) as u32;
+        let row_height = Self::label_height() + self.height + PADDING;
+
+        let width = PADDING + columns * (self.width + PADDING);
+        let height = PADDING + Self::label_height() + self.rows.len() as u32 * row_height;
+        let mut sheet = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+        for (col, time) in self.times.iter().enumerate() {
+            let x = PADDING + col as u32 * (self.width + PADDING);
+            let label = fit_text(&format!("{:.2}s", time), self.width, TEXT_SCALE);
+            draw_text(&mut sheet, x, PADDING, &label, TEXT_SCALE, TEXT);
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            let y = PADDING + Self::label_height() + i as u32 * row_height;
+            let label = fit_text(&row.name, width - 2 * PADDING, TEXT_SCALE);
+            draw_text(&mut sheet, PADDING, y, &label, TEXT_SCALE, TEXT);
+
+            let y = y + Self::label_height();
+            match &row.frames {
+                Ok(frames) => {
+                    for (col, frame) in frames.iter().enumerate() {
+                        let x = PADDING + col as u32 * (self.width + PADDING);
+                        imageops::replace(&mut sheet, frame, x as i64, y as i64);
+                    }
+                }
+                Err(_) => {
+                    let failed_width = width - 2 * PADDING;
+                    let failed = RgbaImage::from_pixel(failed_width, self.height, FAILED);
+                    imageops::replace(&mut sheet, &failed, PADDING as i64, y as i64);
+                    let text_y = y + (self.height.saturating_sub(GLYPH_HEIGHT * TEXT_SCALE)) / 2;
+                    draw_text(&mut sheet, 2 * PADDING, text_y, "FAILED", TEXT_SCALE, TEXT);
+                }
+            }
+        }
+
+        sheet
+    }
+
+    /// Thumbnails are named by row and column, times that round alike would share a name.
+    fn thumbnail_name(row: usize, col: usize) -> String {
+        format!("thumbs/{:03}_{:02}.png", row, col)
+    }
+
+    /// An HTML page listing every row with its thumbnails, linking to `sheet`.
+    pub fn html(&self, sheet: &str) -> String {
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Playlist</title>\n\
+             <style>body {{ background: #181818; color: #e6e6e6; font-family: sans-serif; }} \
+             img {{ margin: 2px; }} pre {{ color: #f08080; }}</style>\n</head>\n<body>"
+        );
+        let _ = writeln!(
+            html,
+            "<h1>{} shaders</h1>\n<p><a href=\"{}\">Contact sheet</a></p>",
+            self.rows.len(),
+            escape_html(sheet)
+        );
+
+        for (i, row) in self.rows.iter().enumerate() {
+            let _ = writeln!(html, "<h2>{}</h2>", escape_html(&row.name));
+            let location = escape_html(row.entry.location());
+            let _ = match &row.entry {
+                Entry::Toy(id) => writeln!(
+                    html,
+                    "<p>toy <a href=\"https://www.shadertoy.com/view/{}\">{}</a></p>",
+                    escape_html(id),
+                    location
+                ),
+                Entry::Local(_) => writeln!(html, "<p>local {}</p>", location),
+                Entry::Source(_) => writeln!(html, "<p>source {}</p>", location),
+            };
+
+            match &row.frames {
+                Ok(_) => {
+                    let _ = write!(html, "<p>");
+                    for (col, &time) in self.times.iter().enumerate() {
+                        let _ = write!(
+                            html,
+                            "<img src=\"{}\" width=\"{}\" height=\"{}\" title=\"{:.2}s\">",
+                            Self::thumbnail_name(i, col),
+                            self.width,
+                            self.height,
+                            time
+                        );
+                    }
+                    let _ = writeln!(html, "</p>");
+                }
+                Err(e) => {
+                    let _ = writeln!(html, "<pre>{}</pre>", escape_html(e));
+                }
+            }
+        }
+
+        let _ = writeln!(html, "</body>\n</html>");
+        html
+    }
+
+    /// Writes `sheet.png`, the thumbnails and `index.html` into `dir`.
+    pub fn save(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(dir.join("thumbs"))?;
+
+        self.compose().save(dir.join("sheet.png"))?;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if let Ok(frames) = &row.frames {
+                for (col, frame) in frames.iter().enumerate() {
+                    frame.save(dir.join(Self::thumbnail_name(i, col)))?;
+                }
+            }
+        }
+
+        let mut index = BufWriter::new(File::create(dir.join("index.html"))?);
+        index.write_all(self.html("sheet.png").as_bytes())?;
+        index.flush()?;
+        Ok(())
+    }
+}
+
+/// Removes the terminal colours shader validation errors come with.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to and including the final letter of the sequence
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
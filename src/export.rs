@@ -0,0 +1,144 @@
+use std::{error::Error, fs::File, io::BufWriter, io::Write, path::Path};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, RgbaImage,
+};
+
+use crate::screenshot::Frame;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AnimFormat {
+    Gif,
+    Apng,
+    Webp,
+}
+
+impl AnimFormat {
+    /// Guesses the format from the file extension, `.png` is written as APNG.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "gif" => Some(AnimFormat::Gif),
+            "png" | "apng" => Some(AnimFormat::Apng),
+            "webp" => Some(AnimFormat::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Frames collected from an `AnimScrot`, ready to be encoded as an animated image.
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    pub frames: Vec<RgbaImage>,
+}
+
+impl Animation {
+    pub fn new(width: u32, height: u32, fps: f32) -> Self {
+        Self {
+            width,
+            height,
+            fps,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, frame: &Frame) {
+        debug_assert_eq!((frame.width, frame.height), (self.width, self.height));
+        self.frames.push(frame.to_image());
+    }
+
+    /// Cross-fades the last `fade` frames into the first ones and drops them,
+    /// so the animation loops without a visible jump.
+    pub fn seamless(&mut self, fade: usize) {
+        let fade = fade.min(self.frames.len() / 2);
+        if fade == 0 {
+            return;
+        }
+
+        let tail = self.frames.split_off(self.frames.len() - fade);
+        for (i, from) in tail.iter().enumerate() {
+            let head = (i + 1) as f32 / (fade + 1) as f32;
+
+            let to = &mut self.frames[i];
+            for (a, b) in to.iter_mut().zip(from.iter()) {
+                *a = (*a as f32 * head + *b as f32 * (1.0 - head)).round() as u8;
+            }
+        }
+    }
+
+    fn frame_ms(&self) -> f32 {
+        1000.0 / self.fps
+    }
+
+    /// GIF with a quantized palette per frame, `speed` goes from 1 (best) to 30 (fastest).
+    pub fn write_gif<W: Write>(&self, w: W, speed: i32) -> Result<(), Box<dyn Error>> {
+        let mut encoder = GifEncoder::new_with_speed(w, speed.clamp(1, 30));
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        let delay = Delay::from_numer_denom_ms((self.frame_ms() * 100.0) as u32, 100);
+        for frame in &self.frames {
+            encoder.encode_frame(image::Frame::from_parts(frame.clone(), 0, 0, delay))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_apng<W: Write>(&self, w: W) -> Result<(), Box<dyn Error>> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        encoder.set_frame_delay((self.frame_ms() * 10.0) as u16, 10000)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in &self.frames {
+            writer.write_image_data(frame.as_raw())?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Lossless when `quality` is `None`, otherwise lossy with a quality from 0 to 100.
+    pub fn write_webp<W: Write>(
+        &self,
+        mut w: W,
+        quality: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut config = webp::WebPConfig::new().map_err(|_| "Could not create webp config")?;
+        match quality {
+            Some(q) => config.quality = q.clamp(0.0, 100.0),
+            None => config.lossless = 1,
+        }
+
+        let mut encoder = webp::AnimEncoder::new(self.width, self.height, &config);
+        encoder.set_loop_count(0);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let timestamp = (i as f32 * self.frame_ms()).round() as i32;
+            encoder.add_frame(webp::AnimFrame::from_rgba(
+                frame.as_raw(),
+                self.width,
+                self.height,
+                timestamp,
+            ));
+        }
+
+        let data = encoder
+            .try_encode()
+            .map_err(|e| format!("Could not encode webp: {:?}", e))?;
+        w.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path, format: AnimFormat) -> Result<(), Box<dyn Error>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            AnimFormat::Gif => self.write_gif(&mut out, 10)?,
+            AnimFormat::Apng => self.write_apng(&mut out)?,
+            AnimFormat::Webp => self.write_webp(&mut out, None)?,
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
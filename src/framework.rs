@@ -11,7 +11,7 @@ use winit::{
 };
 use x11_dl::xlib::Xlib;
 
-use crate::{Args, Event, Renderable, RenderableConfig};
+use crate::{clock::Clock, Args, Event, Renderable, RenderableConfig};
 
 pub struct Rend {
     id: u32,
@@ -244,6 +244,23 @@ pub async fn screen<E: Renderable + RenderableConfig>(
     frame.present();
 }
 
+/// Keyboard controls for the clock of the window loop:
+/// space pauses, left and right step a frame, up and down double or halve the rate
+/// and backspace goes back to the start. Returns whether the key did anything.
+fn control_clock(clock: &mut Clock, key: event::VirtualKeyCode) -> bool {
+    use event::VirtualKeyCode::*;
+    match key {
+        Space => clock.toggle_pause(),
+        Right => clock.step(1),
+        Left => clock.step(-1),
+        Up => clock.set_rate(clock.rate() * 2.0),
+        Down => clock.set_rate(clock.rate() / 2.0),
+        Back => clock.seek(0.0),
+        _ => return false,
+    }
+    true
+}
+
 pub async fn start<E: Renderable + RenderableConfig>(
     Setup {
         window,
@@ -257,6 +274,7 @@ pub async fn start<E: Renderable + RenderableConfig>(
     }: Setup,
     args: Args,
     input: E::Input,
+    mut clock: Clock,
 ) {
     let mut config = surface
         .get_default_config(&adapter, size.width, size.height)
@@ -324,6 +342,22 @@ pub async fn start<E: Renderable + RenderableConfig>(
                 } => {
                     eprintln!("{:#?}", instance.generate_report());
                 }
+                WindowEvent::KeyboardInput {
+                    input:
+                        event::KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state: event::ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if control_clock(&mut clock, key) => {
+                    eprintln!(
+                        "Time {:.3}s, rate {}x{}",
+                        clock.time(),
+                        clock.rate(),
+                        if clock.is_paused() { ", paused" } else { "" }
+                    );
+                }
                 _ => {}
             },
             event::Event::RedrawRequested(_) => {
@@ -342,7 +376,8 @@ pub async fn start<E: Renderable + RenderableConfig>(
                 }
 
                 if !args.single {
-                    example.update(elapsed, (config.width, config.height), &device, &queue);
+                    let time = clock.tick();
+                    example.update(time, (config.width, config.height), &device, &queue);
                 }
 
                 let frame = match surface.get_current_texture() {
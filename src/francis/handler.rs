@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::error::Error;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::time::Instant;
 
@@ -9,16 +10,27 @@ use futures_util::future::select;
 use futures_util::future::Either;
 use serde::{Deserialize, Serialize};
 
+use crate::froxy::froxy_configs_retrying;
 use crate::screenshot::scrot_new;
 use crate::screenshot::AnimScrot;
 use crate::screenshot::Ctx;
+use crate::screenshot::FrameSettings;
+use crate::screenshot::Glitch;
 use crate::shadertoy::Args;
+use crate::shadertoy::Client;
 use crate::shadertoy::Example;
+use crate::shadertoy::ParseMode;
+use crate::shadertoy::SearchQuery;
+use crate::shadertoy::Shader;
 
-use super::froxy_configs;
 use super::server::start_server;
-use super::Francis;
 use super::FroxyConfig;
+use super::Health;
+use super::Order;
+use super::PixelSink;
+use super::Protocol;
+use super::SinkOptions;
+use super::Supervised;
 use nanorand::{Rng, WyRand};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,9 +41,66 @@ pub struct Options {
     toy: Vec<String>,
     /// GLSL source files
     source: Vec<String>,
+    /// Shader toy searches, like "top 20 popular with filter multipass",
+    /// resolved at startup into `toy` entries
+    #[serde(default)]
+    search: Vec<String>,
+    /// Settings per froxy section, in the order froxy lists them.
+    /// Sections without an entry use the defaults
+    #[serde(default)]
+    targets: Vec<TargetOptions>,
 
     francis: String,
     froxy: String,
+    /// How to talk to `francis`
+    #[serde(default)]
+    protocol: Protocol,
+    /// Pixel orders for single shaders by their playlist location, like
+    /// `{"shaders/planet.glsl": "hilbert"}`, overriding the target's order
+    #[serde(default)]
+    orders: HashMap<String, Order>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TargetOptions {
+    /// Supersampling, like `"supersample": 4, "filter": "lanczos"`
+    #[serde(flatten)]
+    pub frame: FrameSettings,
+    /// Overrides the playlist's protocol for this section
+    pub protocol: Option<Protocol>,
+    /// Like `"alpha": true` to let the server blend frames over the canvas,
+    /// `"connections": 4` to draw over several connections or `"order": "hilbert"`
+    #[serde(flatten)]
+    pub sink: SinkOptions,
+}
+
+/// A single shader on the playlist
+#[derive(Debug, Clone)]
+pub enum Entry {
+    /// Downloaded shader toy json
+    Local(String),
+    /// Shader toy id
+    Toy(String),
+    /// GLSL source file
+    Source(String),
+}
+
+impl Entry {
+    /// How the entry is written in the playlist, for messages
+    pub fn location(&self) -> &str {
+        match self {
+            Entry::Local(x) | Entry::Toy(x) | Entry::Source(x) => x,
+        }
+    }
+
+    pub async fn args(&self, client: &Client, w: f32, h: f32) -> Result<Args, Box<dyn Error>> {
+        match self {
+            Entry::Local(local) => Args::from_local(client, local.clone(), w, h).await,
+            Entry::Toy(toy) => Args::from_toy(client, toy.clone(), None, w, h).await,
+            Entry::Source(source) => Args::from_source(Some(source.clone()), w, h).await,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -48,7 +117,13 @@ pub struct Update {
     running: Option<bool>,
     wait: Option<u64>,
     run: Option<u64>,
+    /// The glitch dropout, kept from before there were other glitches
     failure: Option<f32>,
+    dropout: Option<f32>,
+    tearing: Option<f32>,
+    shift: Option<f32>,
+    noise: Option<f32>,
+    blocks: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -63,7 +138,8 @@ struct Params {
     running: bool,
     wait: u64,
     run: u64,
-    failure: f32,
+    /// Applied to the frames of every target
+    glitch: Glitch,
 }
 
 impl Params {
@@ -72,31 +148,43 @@ impl Params {
             running: true,
             wait: 500,
             run: 2000,
-            failure: 0.2,
+            glitch: Glitch {
+                dropout: 0.2,
+                ..Glitch::default()
+            },
         }
     }
 
-    fn update(
-        &mut self,
-        Update {
-            running,
-            wait,
-            run,
-            failure,
-        }: Update,
-    ) {
-        if let Some(r) = running {
+    fn update(&mut self, update: Update) {
+        if let Some(r) = update.running {
             self.running = r;
         }
-        if let Some(r) = wait {
+        if let Some(r) = update.wait {
             self.wait = r;
         }
-        if let Some(r) = run {
+        if let Some(r) = update.run {
             self.run = r;
         }
-        if let Some(r) = failure {
-            self.failure = r;
+        self.glitch = update.glitch(self.glitch);
+    }
+}
+
+impl Update {
+    /// `glitch` with the glitch settings of this update applied,
+    /// `failure` sets the dropout unless the dropout is given as well
+    pub fn glitch(&self, mut glitch: Glitch) -> Glitch {
+        for (value, param) in [
+            (self.dropout.or(self.failure), &mut glitch.dropout),
+            (self.tearing, &mut glitch.tearing),
+            (self.shift, &mut glitch.shift),
+            (self.noise, &mut glitch.noise),
+            (self.blocks, &mut glitch.blocks),
+        ] {
+            if let Some(r) = value {
+                *param = r;
+            }
         }
+        glitch
     }
 }
 
@@ -118,7 +206,11 @@ impl Current {
 
 pub struct Handler {
     toys: Vec<AnimScrot<Example>>,
-    clients: Vec<Francis>,
+    /// Pixel order of every toy, when it overrides the target's
+    orders: Vec<Option<Order>>,
+    clients: Vec<Supervised>,
+    targets: Vec<TargetOptions>,
+    info: Arc<Mutex<Info>>,
 
     commands: mpsc::Receiver<Command>,
     rand: WyRand,
@@ -136,6 +228,8 @@ pub struct Handler {
 pub struct Info {
     froxy: Vec<FroxyConfig>,
     toys: Vec<String>,
+    /// Health of every target, in froxy order
+    health: Vec<Health>,
 }
 
 async fn create_scrot(
@@ -149,46 +243,111 @@ async fn create_scrot(
     Ok((anim, name))
 }
 
+/// Resolves every search in the playlist into shader toy ids.
+/// Results are cached in the `search` directory of the client's cache,
+/// so repeated starts don't hit the API.
+async fn resolve_searches(client: &Client, searches: &[String]) -> Vec<String> {
+    let mut ids = Vec::new();
+    for search in searches {
+        let resolved = match search.parse::<SearchQuery>() {
+            Ok(query) => query.resolve(client, true).await,
+            Err(e) => Err(e),
+        };
+
+        match resolved {
+            Ok(found) => {
+                println!("Search '{}' resolved to {} shaders", search, found.len());
+                ids.extend(found);
+            }
+            Err(e) => eprintln!("Search '{}' failed: {}", search, e),
+        }
+    }
+    ids
+}
+
+impl Options {
+    /// Every shader on the playlist, local ones first, then toys including
+    /// the search results, then sources.
+    pub async fn entries(&self, client: &Client) -> Vec<Entry> {
+        let found = resolve_searches(client, &self.search).await;
+
+        let locals = self.local.iter().cloned().map(Entry::Local);
+        let toys = self.toy.iter().cloned().chain(found).map(Entry::Toy);
+        let sources = self.source.iter().cloned().map(Entry::Source);
+        locals.chain(toys).chain(sources).collect()
+    }
+
+    /// Downloads every shader and texture on the playlist into the client's cache,
+    /// so the playlist can run in offline mode. Returns how many resources were downloaded.
+    pub async fn prefetch(&self, client: &Client) -> Result<usize, Box<dyn Error>> {
+        let mut toys = self.toy.clone();
+        toys.extend(resolve_searches(client, &self.search).await);
+
+        let mut fetched = 0;
+        for id in &toys {
+            let shader = client.get_shader(id, None).await?;
+            fetched += 1 + client.prefetch(&shader).await?;
+        }
+
+        for local in &self.local {
+            let st = async_std::fs::read_to_string(local).await?;
+            let shader = Shader::from_json(&st, ParseMode::Lenient)?;
+            fetched += client.prefetch(&shader).await?;
+        }
+
+        Ok(fetched)
+    }
+}
+
 use futures_util::{stream, StreamExt};
 impl Handler {
-    pub async fn new(api: &str, input: Options, port: u16) -> std::io::Result<Handler> {
+    pub async fn new(client: Client, input: Options, port: u16) -> std::io::Result<Handler> {
         let rand = WyRand::new();
 
-        let froxy = froxy_configs(&input.froxy).await?;
+        let entries = input.entries(&client).await;
+        let orders = entries
+            .iter()
+            .map(|entry| input.orders.get(entry.location()).copied())
+            .collect();
+
+        let froxy = froxy_configs_retrying(&input.froxy).await;
 
         println!("got froxy config");
 
-        let clients: Vec<_> = stream::iter(&froxy)
-            .then(|fr| Francis::new(&input.francis, *fr))
-            .map(|x| x.unwrap())
-            .collect()
-            .await;
+        let mut targets = input.targets.clone();
+        targets.resize(froxy.len(), TargetOptions::default());
+
+        let mut clients = Vec::with_capacity(froxy.len());
+        for (i, (fr, target)) in froxy.iter().zip(&targets).enumerate() {
+            let protocol = target.protocol.unwrap_or(input.protocol);
+            let client = Supervised::connect(&input.francis, protocol, *fr, target.sink)
+                .await
+                .map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("Target {} is misconfigured: {}", i, e))
+                })?;
+            clients.push(client);
+        }
 
         println!("got francis clients");
 
-        let (w, h) = froxy.iter().fold((0, 0), |(w, h), froxy| {
-            (w.max(froxy.width), h.max(froxy.height))
-        });
-        let (w, h) = (w.into(), h.into());
+        // Toys are created once, large enough for every target
+        let (w, h) = froxy
+            .iter()
+            .zip(&targets)
+            .map(|(froxy, target)| {
+                let size = (froxy.width.into(), froxy.height.into());
+                target.frame.render_size(size)
+            })
+            .fold((0, 0), |(w, h), (fw, fh)| (w.max(fw), h.max(fh)));
 
         let (wf, hf) = (w as f32, h as f32);
 
         let ctx = Ctx::new::<Example>().await;
         let mut options = HashMap::new();
 
-        let locals = stream::iter(input.local)
-            .then(|local| Args::from_local(api, local, wf, hf))
-            .map(|x| x.unwrap());
-        let toys = stream::iter(input.toy)
-            .then(|toy| Args::from_toy(api, toy, None, wf, hf))
-            .map(|x| x.unwrap());
-        let sources = stream::iter(input.source)
-            .then(|toy| Args::from_source(Some(toy), wf, hf))
-            .map(|x| x.unwrap());
-
-        let toys_and_names: Vec<_> = locals
-            .chain(toys)
-            .chain(sources)
+        let toys_and_names: Vec<_> = stream::iter(&entries)
+            .then(|entry| entry.args(&client, wf, hf))
+            .map(|x| x.unwrap())
             .then(|args| create_scrot(&ctx, w, h, args))
             .map(|x| x.unwrap())
             .collect()
@@ -207,22 +366,25 @@ impl Handler {
                 names.push(n)
             });
 
-        let info = Info {
+        let info = Arc::new(Mutex::new(Info {
             froxy,
             toys: options.keys().cloned().collect(),
-        };
-        let info = serde_json::to_string_pretty(&info).unwrap();
+            health: clients.iter().map(Supervised::health).collect(),
+        }));
 
         let (tx, rx) = mpsc::bounded(10);
 
         println!("Server starting");
-        tokio::spawn(start_server(port, tx, info));
+        tokio::spawn(start_server(port, tx, info.clone()));
 
         Ok(Self {
             ctx,
             start: Instant::now(),
             toys,
+            orders,
             clients,
+            targets,
+            info,
             rand,
             names,
             commands: rx,
@@ -234,6 +396,7 @@ impl Handler {
     pub async fn start(mut self) -> Result<(), Box<dyn Error>> {
         loop {
             if self.current.end < Instant::now() {
+                self.supervise().await;
                 match select(
                     Box::pin(sleep(Duration::from_millis(self.params.wait))),
                     self.commands.recv(),
@@ -250,7 +413,7 @@ impl Handler {
                 };
             }
             while self.current.end > Instant::now() {
-                self.frame(self.params.failure).await?;
+                self.frame().await?;
                 if let Ok(x) = self.commands.try_recv() {
                     self.handle_command(x);
                 }
@@ -258,11 +421,36 @@ impl Handler {
         }
     }
 
+    /// Reconnects the targets that are down and due for another attempt.
+    async fn supervise(&mut self) {
+        for client in &mut self.clients {
+            client.reconnect().await;
+        }
+        self.publish_health();
+    }
+
+    fn publish_health(&self) {
+        let health = self.clients.iter().map(Supervised::health).collect();
+        self.info.lock().unwrap().health = health;
+    }
+
+    /// The requested target, or a random healthy one when there is no such target.
+    /// When every target is down any of them is picked, so it's retried.
     fn francis_idx(&mut self, run: &Send) -> usize {
-        if let Some(ref st) = run.target {
-            *st
-        } else {
+        if let Some(target) = run.target {
+            if target < self.clients.len() {
+                return target;
+            }
+            eprintln!("There is no target {}, picking another one", target);
+        }
+
+        let healthy: Vec<_> = (0..self.clients.len())
+            .filter(|&i| self.clients[i].is_healthy())
+            .collect();
+        if healthy.is_empty() {
             self.rand.generate_range(0usize..self.clients.len())
+        } else {
+            healthy[self.rand.generate_range(0usize..healthy.len())]
         }
     }
 
@@ -287,27 +475,56 @@ impl Handler {
         }
     }
 
-    async fn frame(&mut self, failure: f32) -> Result<(), Box<dyn Error>> {
+    async fn frame(&mut self) -> Result<(), Box<dyn Error>> {
         let francis = &mut self.clients[self.current.francis_idx];
+        let settings = FrameSettings {
+            glitch: self.params.glitch,
+            ..self.targets[self.current.francis_idx].frame
+        };
         let toy = &mut self.toys[self.current.shader_idx];
-        let frame = toy
-            .frame(
+
+        // Keep the GPU busy with the next frames while this one is sent
+        let size = Some((francis.width(), francis.height()));
+        while toy.in_flight() < toy.depth() {
+            toy.submit_with(
                 &self.ctx,
                 self.start.elapsed().as_secs_f32(),
-                Some((francis.width(), francis.height())),
-            )
-            .await;
+                size,
+                &settings,
+            );
+        }
+        let frame = match toy.receive(&self.ctx).await.expect("frames are in flight") {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("Reading back a frame failed: {}", e);
+                self.current.end = Instant::now();
+                return Ok(());
+            }
+        };
 
-        francis.write(frame.buffer, 4, failure).await?;
+        // A failing target ends its run, the next one is picked among the healthy targets
+        if let Err(e) = francis.write(frame.buffer, 4).await {
+            eprintln!("Target {} failed: {}", self.current.francis_idx, e);
+            self.current.end = Instant::now();
+            self.publish_health();
+        }
         Ok(())
     }
 
     fn update_current(&mut self, send: Send) {
+        // Frames in flight are rendered for the previous target
+        self.toys[self.current.shader_idx].discard(&self.ctx);
+
         let francis_idx = self.francis_idx(&send);
         let shader_idx = self.shader_idx(&send);
 
         let duration = self.duration(&send);
 
+        let order = self.orders[shader_idx].unwrap_or(self.targets[francis_idx].sink.order);
+        self.clients[francis_idx].set_order(order);
+        // The last frame of the shader was for another run, maybe on another target
+        self.toys[shader_idx].forget_previous();
+
         self.current = Current {
             shader_idx,
             end: Instant::now() + Duration::from_millis(duration),
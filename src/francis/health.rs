@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use async_std::io;
+use serde::Serialize;
+
+use super::{FroxyConfig, Order, PixelSink, Protocol, SinkOptions};
+use crate::backoff::Backoff;
+
+/// How long a connection attempt may take before the target counts as down
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Health of a target, as shown by the info endpoint
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum Health {
+    Healthy,
+    /// Disconnected, reconnects are attempted with a growing delay
+    Down {
+        failures: u32,
+        error: String,
+    },
+}
+
+/// A sink that reconnects when its connection fails.
+/// While it's down writes fail fast until the next reconnect is due.
+pub struct Supervised {
+    addr: String,
+    protocol: Protocol,
+    region: FroxyConfig,
+    options: SinkOptions,
+    sink: Option<Box<dyn PixelSink>>,
+    backoff: Backoff,
+    error: String,
+    retry_at: Instant,
+}
+
+impl Supervised {
+    /// Connects to `addr`, a failed connection is retried later instead of returned.
+    /// Only an invalid configuration, which no retry fixes, is an error.
+    pub async fn connect(
+        addr: &str,
+        protocol: Protocol,
+        region: FroxyConfig,
+        options: SinkOptions,
+    ) -> io::Result<Self> {
+        let mut this = Self {
+            addr: addr.to_string(),
+            protocol,
+            region,
+            options,
+            sink: None,
+            backoff: Backoff::default(),
+            error: String::new(),
+            retry_at: Instant::now(),
+        };
+        match this.attempt().await {
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => return Err(e),
+            Err(e) => this.fail(e),
+            Ok(()) => {}
+        }
+        Ok(this)
+    }
+
+    pub fn health(&self) -> Health {
+        match self.sink {
+            Some(_) => Health::Healthy,
+            None => Health::Down {
+                failures: self.backoff.failures(),
+                error: self.error.clone(),
+            },
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Reconnects when the sink is down and the next attempt is due.
+    /// Returns whether the sink is connected.
+    pub async fn reconnect(&mut self) -> bool {
+        if self.sink.is_some() {
+            return true;
+        }
+        if Instant::now() < self.retry_at {
+            return false;
+        }
+
+        if let Err(e) = self.attempt().await {
+            self.fail(e);
+        }
+        self.sink.is_some()
+    }
+
+    async fn attempt(&mut self) -> io::Result<()> {
+        let connect = self
+            .protocol
+            .connect(&self.addr, Some(self.region), self.options);
+        let sink = io::timeout(CONNECT_TIMEOUT, connect).await?;
+        if self.backoff.failures() > 0 {
+            eprintln!("Reconnected to {}", self.addr);
+        }
+        self.sink = Some(sink);
+        self.backoff.reset();
+        Ok(())
+    }
+
+    fn fail(&mut self, error: io::Error) {
+        self.sink = None;
+        self.backoff.fail();
+        self.error = error.to_string();
+        self.retry_at = Instant::now() + self.backoff.delay();
+        eprintln!(
+            "{} is down: {}, retrying in {:?}",
+            self.addr,
+            error,
+            self.backoff.delay()
+        );
+    }
+}
+
+#[async_trait::async_trait]
+impl PixelSink for Supervised {
+    fn width(&self) -> u32 {
+        self.region.width as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.region.height as u32
+    }
+
+    async fn write(&mut self, buf: Vec<u8>, bytes_per_pixel: usize) -> io::Result<usize> {
+        if !self.reconnect().await {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("{} is down: {}", self.addr, self.error),
+            ));
+        }
+
+        let sink = self.sink.as_mut().expect("reconnected");
+        match sink.write(buf, bytes_per_pixel).await {
+            Ok(sent) => Ok(sent),
+            Err(e) => {
+                let error = io::Error::new(e.kind(), e.to_string());
+                self.fail(e);
+                Err(error)
+            }
+        }
+    }
+
+    fn set_order(&mut self, order: Order) {
+        self.options.order = order;
+        if let Some(sink) = &mut self.sink {
+            sink.set_order(order);
+        }
+    }
+}
@@ -3,5 +3,12 @@ pub use client::*;
 mod handler; 
 pub use handler::*;
 mod server;
-mod froxy;
-pub use froxy::*;
+pub use crate::froxy::FroxyConfig;
+mod sink;
+pub use sink::*;
+mod tiled;
+pub use tiled::*;
+mod health;
+pub use health::*;
+mod order;
+pub use order::*;
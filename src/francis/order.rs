@@ -0,0 +1,106 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// In which order the pixels of a frame are sent, which decides how a frame fills in
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// A new random permutation of all pixels every frame
+    #[default]
+    Random,
+    /// Along a Hilbert curve, filling in neighbourhood by neighbourhood
+    Hilbert,
+    /// Along a Z-order (Morton) curve
+    ZOrder,
+    /// Row by row, left to right
+    Scanline,
+    /// The pixels that changed the most first
+    Importance,
+    /// Ordered dithering, an evenly spread out set of pixels first, then the gaps
+    Dithered,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(Order::Random),
+            "hilbert" => Ok(Order::Hilbert),
+            "zorder" | "z-order" | "morton" => Ok(Order::ZOrder),
+            "scanline" => Ok(Order::Scanline),
+            "importance" => Ok(Order::Importance),
+            "dithered" | "bayer" => Ok(Order::Dithered),
+            _ => Err(format!(
+                "Unknown order '{}', expected random, hilbert, zorder, scanline, importance or dithered",
+                s
+            )),
+        }
+    }
+}
+
+impl Order {
+    /// Indices of the pixels of a `width` x `height` frame, row by row, in this order.
+    /// Random and importance orders change every frame, they start out as scanlines.
+    pub fn indices(self, width: u16, height: u16) -> Vec<u32> {
+        let mut indices: Vec<u32> = (0..width as u32 * height as u32).collect();
+        let xy = |i: u32| (i % width as u32, i / width as u32);
+
+        match self {
+            Order::Random | Order::Scanline | Order::Importance => {}
+            Order::Hilbert => {
+                let n = (width.max(height) as u32).next_power_of_two();
+                indices.sort_by_cached_key(|&i| {
+                    let (x, y) = xy(i);
+                    hilbert(n, x, y)
+                });
+            }
+            Order::ZOrder => indices.sort_by_cached_key(|&i| {
+                let (x, y) = xy(i);
+                spread(x) | spread(y) << 1
+            }),
+            Order::Dithered => indices.sort_by_cached_key(|&i| {
+                let (x, y) = xy(i);
+                (bayer(x, y), i)
+            }),
+        }
+        indices
+    }
+}
+
+/// Distance of `x`, `y` along the Hilbert curve filling a `n` x `n` square
+fn hilbert(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // Rotate the quadrant so the curve continues where it left off
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Spreads the bits of `v` apart, leaving a zero bit between every two
+fn spread(v: u32) -> u64 {
+    (0..16).fold(0, |acc, bit| acc | (((v as u64) >> bit) & 1) << (2 * bit))
+}
+
+/// Rank of `x`, `y` in an 8 x 8 Bayer matrix
+fn bayer(x: u32, y: u32) -> u32 {
+    const BITS: u32 = 3;
+    (0..BITS).fold(0, |acc, bit| {
+        let (xb, yb) = ((x >> bit) & 1, (y >> bit) & 1);
+        acc | (2 * (xb ^ yb) + yb) << (2 * (BITS - 1 - bit))
+    })
+}
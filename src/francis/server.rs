@@ -4,9 +4,9 @@ use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server};
 use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use super::Command;
+use super::{Command, Info};
 
 error_chain::error_chain! {
     errors {
@@ -33,15 +33,20 @@ async fn handle_post(context: mpsc::Sender<Command>, body: Body) -> Result<Respo
     Ok(Response::new(Body::from("Aight")))
 }
 
+fn handle_get(info: &Mutex<Info>) -> Result<Response<Body>> {
+    let info = serde_json::to_string_pretty(&*info.lock().unwrap())?;
+    Ok(Response::new(Body::from(info)))
+}
+
 async fn handle(
     context: mpsc::Sender<Command>,
     req: Request<Body>,
-    info: Arc<String>,
+    info: Arc<Mutex<Info>>,
 ) -> std::result::Result<Response<Body>, Infallible> {
     let (parts, body) = req.into_parts();
     let resp = match parts.method {
         Method::POST => handle_post(context, body).await,
-        Method::GET => Ok(Response::new(Body::from(info.to_string()))),
+        Method::GET => handle_get(&info),
         _ => Err(ErrorKind::InvalidMethod(parts.method).into()),
     };
 
@@ -51,12 +56,10 @@ async fn handle(
     }
 }
 
-pub async fn start_server(port: u16, tx: mpsc::Sender<Command>, info: String) {
+pub async fn start_server(port: u16, tx: mpsc::Sender<Command>, info: Arc<Mutex<Info>>) {
     // Construct our SocketAddr to listen on...
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
 
-    let info = Arc::new(info);
-
     // Shared is a MakeService that produces services by cloning an inner service...
     let make_service = make_service_fn(move |_conn: &AddrStream| {
         let tx = tx.clone();
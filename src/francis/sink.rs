@@ -0,0 +1,442 @@
+use std::{
+    cmp::Reverse,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use async_std::{
+    io::{self, prelude::BufReadExt, BufReader, WriteExt},
+    net::{TcpStream, ToSocketAddrs},
+};
+use nanorand::{Rng, WyRand};
+use serde::{Deserialize, Serialize};
+
+use super::{Francis, FroxyConfig, Order, Tiled};
+
+/// Something frames can be drawn on, like a francis server or a pixelflut canvas.
+#[async_trait::async_trait]
+pub trait PixelSink: Send {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+
+    /// Sends the pixels of `buf` that changed since the last frame, in the sink's order.
+    /// `buf` is BGRA-ordered with `bytes_per_pixel` bytes per pixel.
+    /// Returns how many pixels were sent.
+    async fn write(&mut self, buf: Vec<u8>, bytes_per_pixel: usize) -> io::Result<usize>;
+
+    /// Changes the order pixels are sent in, from the next frame on.
+    fn set_order(&mut self, order: Order);
+}
+
+/// Wire protocol of a pixel sink
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Francis' 7 byte big endian x, y, r, g, b records
+    #[default]
+    Binary,
+    /// The pixelflut text protocol, `PX x y rrggbb`
+    Text,
+    /// The binary pixelflut extension, `PB` with little endian x, y and r, g, b, a
+    Pb,
+    /// Little endian 16 bit x, y followed by r, g, b, and a when sending alpha
+    Packed,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "binary" => Ok(Protocol::Binary),
+            "text" | "pixelflut" => Ok(Protocol::Text),
+            "pb" => Ok(Protocol::Pb),
+            "packed" => Ok(Protocol::Packed),
+            _ => Err(format!(
+                "Unknown protocol '{}', expected binary, text, pb or packed",
+                s
+            )),
+        }
+    }
+}
+
+/// How a sink talks to its server, besides the protocol
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SinkOptions {
+    /// Send the region's position once with the pixelflut `OFFSET` command,
+    /// instead of adding it to every pixel
+    pub offset: bool,
+    /// Send the alpha of the frame, so the server blends it over the canvas
+    pub alpha: bool,
+    /// Split the region into this many tiles, each drawn over its own connection
+    pub connections: usize,
+    /// Pixels encoded into a single write
+    pub batch: usize,
+    /// Only send the pixels that changed since they were last sent
+    pub diff: bool,
+    /// How far a channel may drift from what was last sent before the pixel is sent again
+    pub threshold: u8,
+    /// Milliseconds between full refreshes, which repair pixels others drew over
+    pub refresh: Option<u64>,
+    /// In which order the pixels of a frame are sent
+    pub order: Order,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        Self {
+            offset: false,
+            alpha: false,
+            connections: 1,
+            batch: 5000,
+            diff: true,
+            threshold: 0,
+            refresh: None,
+            order: Order::Random,
+        }
+    }
+}
+
+impl Protocol {
+    /// Connects to the sink at `addr` drawing in `region`, over `options.connections`
+    /// connections. Pixelflut servers are asked for their size when there's no region,
+    /// the francis protocol needs one.
+    pub async fn connect(
+        self,
+        addr: &str,
+        region: Option<FroxyConfig>,
+        options: SinkOptions,
+    ) -> io::Result<Box<dyn PixelSink>> {
+        let unsupported = |what| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the francis protocol {}", what),
+            ))
+        };
+
+        if self == Protocol::Binary {
+            if options.alpha {
+                return unsupported("has no alpha");
+            }
+            if options.offset {
+                return unsupported("has no offset command");
+            }
+            if region.is_none() {
+                return unsupported("can't discover its region");
+            }
+        }
+
+        let region = match region {
+            Some(region) => region,
+            None => {
+                let (width, height) = Pixelflut::canvas_size(addr).await?;
+                FroxyConfig {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                    port: 0,
+                }
+            }
+        };
+
+        Ok(Box::new(Tiled::connect(addr, self, region, options).await?))
+    }
+
+    /// Connects a single connection drawing in `region`.
+    pub(super) async fn connect_one(
+        self,
+        addr: &str,
+        region: FroxyConfig,
+        options: SinkOptions,
+    ) -> io::Result<Box<dyn PixelSink>> {
+        Ok(match self {
+            Protocol::Binary => Box::new(Francis::new(addr, region, options).await?),
+            _ => Box::new(Pixelflut::new(addr, self, region, options).await?),
+        })
+    }
+}
+
+/// Checks that every pixel of `region` has a canvas coordinate that fits a u16.
+pub(super) fn check_region(region: FroxyConfig) -> io::Result<()> {
+    let fits = |start: u16, size: u16| start as u32 + size as u32 <= u16::MAX as u32 + 1;
+    if fits(region.x, region.width) && fits(region.y, region.height) {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "the region x {} y {} w {} h {} reaches past the largest coordinate {}",
+            region.x,
+            region.y,
+            region.width,
+            region.height,
+            u16::MAX
+        ),
+    ))
+}
+
+/// A pixel of a region, in region coordinates
+#[derive(Copy, Clone, Debug)]
+pub struct Pixel {
+    pub x: u16,
+    pub y: u16,
+    pub rgba: [u8; 4],
+}
+
+/// Picks the pixels of a frame to send: in the configured order, leaving out the ones
+/// that didn't change since they were last sent.
+pub struct Scatter {
+    width: u16,
+    height: u16,
+    order: Order,
+    /// Pixel indices in the order they are sent
+    indices: Vec<u32>,
+    /// The colour every pixel was last sent with
+    sent: Vec<u8>,
+    /// Whether the pixel was sent since the last full refresh
+    drawn: Vec<bool>,
+    diff: bool,
+    threshold: u8,
+    refresh: Option<Duration>,
+    refreshed: Instant,
+    rand: WyRand,
+}
+
+impl Scatter {
+    pub fn new(width: u16, height: u16, options: SinkOptions) -> Self {
+        Self {
+            width,
+            height,
+            order: options.order,
+            indices: options.order.indices(width, height),
+            sent: Vec::new(),
+            drawn: vec![false; width as usize * height as usize],
+            diff: options.diff,
+            threshold: options.threshold,
+            refresh: options.refresh.map(Duration::from_millis),
+            refreshed: Instant::now(),
+            rand: WyRand::new(),
+        }
+    }
+
+    pub fn set_order(&mut self, order: Order) {
+        if order != self.order {
+            self.order = order;
+            self.indices = order.indices(self.width, self.height);
+        }
+    }
+
+    /// Forgets what was sent, so the next frame is sent whole.
+    pub fn refresh(&mut self) {
+        self.drawn.fill(false);
+        self.refreshed = Instant::now();
+    }
+
+    /// Sorts the pixels by how much they changed since they were last sent,
+    /// pixels that weren't sent yet first. Ties are in random order.
+    fn sort_by_importance(&mut self, buf: &[u8], bytes_per_pixel: usize) {
+        self.rand.shuffle(&mut self.indices);
+        self.indices.sort_by_cached_key(|&pixel| {
+            let pixel = pixel as usize;
+            if !self.drawn[pixel] {
+                return Reverse(u32::MAX);
+            }
+            let index = pixel * bytes_per_pixel;
+            let change = buf[index..index + bytes_per_pixel]
+                .iter()
+                .zip(&self.sent[index..index + bytes_per_pixel])
+                .map(|(new, old)| new.abs_diff(*old) as u32)
+                .sum();
+            Reverse(change)
+        });
+    }
+
+    /// The pixels of `buf` to send.
+    pub fn pixels(&mut self, buf: Vec<u8>, bytes_per_pixel: usize) -> Vec<Pixel> {
+        debug_assert_eq!(buf.len(), bytes_per_pixel * self.indices.len());
+
+        if self.sent.len() != buf.len() {
+            self.sent = vec![0; buf.len()];
+            self.refresh();
+        }
+        if matches!(self.refresh, Some(refresh) if self.refreshed.elapsed() >= refresh) {
+            self.refresh();
+        }
+
+        match self.order {
+            Order::Random => self.rand.shuffle(&mut self.indices),
+            Order::Importance => self.sort_by_importance(&buf, bytes_per_pixel),
+            _ => {}
+        }
+
+        let mut pixels = Vec::new();
+        for &pixel in &self.indices {
+            let pixel = pixel as usize;
+            let index = pixel * bytes_per_pixel;
+            let colour = &buf[index..index + bytes_per_pixel];
+            let sent = &mut self.sent[index..index + bytes_per_pixel];
+
+            let changed = colour
+                .iter()
+                .zip(sent.iter())
+                .any(|(new, old)| new.abs_diff(*old) > self.threshold);
+            if self.diff && self.drawn[pixel] && !changed {
+                continue;
+            }
+
+            sent.copy_from_slice(colour);
+            self.drawn[pixel] = true;
+
+            let a = if bytes_per_pixel > 3 { colour[3] } else { 255 };
+            pixels.push(Pixel {
+                x: (pixel % self.width as usize) as u16,
+                y: (pixel / self.width as usize) as u16,
+                rgba: [colour[2], colour[1], colour[0], a],
+            });
+        }
+
+        pixels
+    }
+}
+
+/// A pixelflut server, spoken to with the text protocol or one of the binary extensions.
+pub struct Pixelflut {
+    protocol: Protocol,
+    region: FroxyConfig,
+    options: SinkOptions,
+    stream: TcpStream,
+    scatter: Scatter,
+}
+
+impl Pixelflut {
+    /// Connects to `addr`, drawing in `region`.
+    pub async fn new<A: ToSocketAddrs + std::fmt::Display>(
+        addr: A,
+        protocol: Protocol,
+        region: FroxyConfig,
+        options: SinkOptions,
+    ) -> io::Result<Self> {
+        check_region(region)?;
+        let mut stream = TcpStream::connect(&addr).await?;
+
+        eprintln!(
+            "Connecting to pixelflut {} ({:?}) x {} y {} w {} h {}",
+            addr, protocol, region.x, region.y, region.width, region.height
+        );
+
+        if options.offset {
+            stream
+                .write_all(format!("OFFSET {} {}\n", region.x, region.y).as_bytes())
+                .await?;
+        }
+
+        Ok(Self {
+            protocol,
+            region,
+            options,
+            stream,
+            scatter: Scatter::new(region.width, region.height, options),
+        })
+    }
+
+    /// Asks the server at `addr` for the size of its canvas.
+    pub async fn canvas_size<A: ToSocketAddrs>(addr: A) -> io::Result<(u16, u16)> {
+        let mut stream = TcpStream::connect(addr).await?;
+        Self::size(&mut stream).await
+    }
+
+    /// Asks the server for the canvas size, it answers `SIZE <width> <height>`.
+    async fn size(stream: &mut TcpStream) -> io::Result<(u16, u16)> {
+        stream.write_all(b"SIZE\n").await?;
+
+        let mut line = String::new();
+        BufReader::new(stream.clone()).read_line(&mut line).await?;
+
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid SIZE response '{}'", line.trim()),
+            )
+        };
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some("SIZE") {
+            return Err(invalid());
+        }
+        let width = parts
+            .next()
+            .and_then(|w| w.parse().ok())
+            .ok_or_else(invalid)?;
+        let height = parts
+            .next()
+            .and_then(|h| h.parse().ok())
+            .ok_or_else(invalid)?;
+        Ok((width, height))
+    }
+
+    fn encode(&self, batch: &mut Vec<u8>, x: u16, y: u16, [r, g, b, a]: [u8; 4]) {
+        let alpha = self.options.alpha;
+        match self.protocol {
+            Protocol::Text if alpha => {
+                let line = format!("PX {} {} {:02x}{:02x}{:02x}{:02x}\n", x, y, r, g, b, a);
+                batch.extend_from_slice(line.as_bytes());
+            }
+            Protocol::Text => {
+                let line = format!("PX {} {} {:02x}{:02x}{:02x}\n", x, y, r, g, b);
+                batch.extend_from_slice(line.as_bytes());
+            }
+            Protocol::Pb => {
+                batch.extend_from_slice(b"PB");
+                batch.extend_from_slice(&x.to_le_bytes());
+                batch.extend_from_slice(&y.to_le_bytes());
+                // PB always has an alpha byte, opaque unless alpha is sent
+                batch.extend_from_slice(&[r, g, b, if alpha { a } else { 255 }]);
+            }
+            Protocol::Packed => {
+                batch.extend_from_slice(&x.to_le_bytes());
+                batch.extend_from_slice(&y.to_le_bytes());
+                batch.extend_from_slice(&[r, g, b]);
+                if alpha {
+                    batch.push(a);
+                }
+            }
+            Protocol::Binary => unreachable!("francis has its own sink"),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PixelSink for Pixelflut {
+    fn width(&self) -> u32 {
+        self.region.width as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.region.height as u32
+    }
+
+    async fn write(&mut self, buf: Vec<u8>, bytes_per_pixel: usize) -> io::Result<usize> {
+        let (dx, dy) = if self.options.offset {
+            (0, 0)
+        } else {
+            (self.region.x, self.region.y)
+        };
+
+        let pixels = self.scatter.pixels(buf, bytes_per_pixel);
+        let mut batch = Vec::new();
+        for chunk in pixels.chunks(self.options.batch.max(1)) {
+            batch.clear();
+            for &Pixel { x, y, rgba } in chunk {
+                self.encode(&mut batch, x + dx, y + dy, rgba);
+            }
+            self.stream.write_all(&batch).await?;
+        }
+        self.stream.flush().await?;
+        Ok(pixels.len())
+    }
+
+    fn set_order(&mut self, order: Order) {
+        self.scatter.set_order(order);
+    }
+}
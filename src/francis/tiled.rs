@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use async_std::io;
+use futures_util::future::try_join_all;
+
+use super::{FroxyConfig, Order, PixelSink, Protocol, SinkOptions};
+
+/// How often the pixel rates are reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A strip of rows of the region, drawn over its own connection.
+struct Tile {
+    addr: String,
+    region: FroxyConfig,
+    sink: Box<dyn PixelSink>,
+    /// Pixels sent since the last report
+    sent: usize,
+}
+
+impl Tile {
+    /// Writes the tile. A failed connection is returned as is,
+    /// reconnecting with a timeout and backoff is up to the supervising sink.
+    async fn write(&mut self, buf: &[u8], bytes_per_pixel: usize) -> io::Result<usize> {
+        let sent = self
+            .sink
+            .write(buf.to_vec(), bytes_per_pixel)
+            .await
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "connection for rows {}..{} failed: {}",
+                        self.region.y,
+                        self.region.y + self.region.height,
+                        e
+                    ),
+                )
+            })?;
+        self.sent += sent;
+        Ok(sent)
+    }
+}
+
+/// A sink splitting its region into strips of rows,
+/// written concurrently over a connection each.
+pub struct Tiled {
+    region: FroxyConfig,
+    tiles: Vec<Tile>,
+    since: Instant,
+}
+
+impl Tiled {
+    pub async fn connect(
+        addr: &str,
+        protocol: Protocol,
+        region: FroxyConfig,
+        options: SinkOptions,
+    ) -> io::Result<Self> {
+        let count = options.connections.clamp(1, region.height.max(1) as usize);
+
+        let mut tiles = Vec::with_capacity(count);
+        for i in 0..count {
+            let top = region.height as usize * i / count;
+            let bottom = region.height as usize * (i + 1) / count;
+            let tile = FroxyConfig {
+                y: region.y + top as u16,
+                height: (bottom - top) as u16,
+                ..region
+            };
+            tiles.push(Tile {
+                addr: addr.to_string(),
+                region: tile,
+                sink: protocol.connect_one(addr, tile, options).await?,
+                sent: 0,
+            });
+        }
+
+        Ok(Self {
+            region,
+            tiles,
+            since: Instant::now(),
+        })
+    }
+
+    fn report(&mut self) {
+        let elapsed = self.since.elapsed();
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            eprintln!(
+                "{} connection {}: {:.0} px/s",
+                tile.addr,
+                i,
+                tile.sent as f32 / elapsed.as_secs_f32()
+            );
+            tile.sent = 0;
+        }
+        self.since = Instant::now();
+    }
+}
+
+#[async_trait::async_trait]
+impl PixelSink for Tiled {
+    fn width(&self) -> u32 {
+        self.region.width as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.region.height as u32
+    }
+
+    async fn write(&mut self, buf: Vec<u8>, bytes_per_pixel: usize) -> io::Result<usize> {
+        let (y, row) = (self.region.y, self.region.width as usize * bytes_per_pixel);
+        let writes = self.tiles.iter_mut().map(|tile| {
+            let top = (tile.region.y - y) as usize;
+            let rows = &buf[top * row..(top + tile.region.height as usize) * row];
+            tile.write(rows, bytes_per_pixel)
+        });
+        let sent = try_join_all(writes).await?.iter().sum();
+
+        self.report();
+        Ok(sent)
+    }
+
+    fn set_order(&mut self, order: Order) {
+        for tile in &mut self.tiles {
+            tile.sink.set_order(order);
+        }
+    }
+}
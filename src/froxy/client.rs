@@ -0,0 +1,40 @@
+use async_std::{
+    io::ReadExt,
+    net::{TcpStream, ToSocketAddrs},
+    task::sleep,
+};
+
+use super::{parse, FroxyConfig, Result};
+use crate::backoff::Backoff;
+
+/// Asks the froxy at `addr` for its sections.
+pub async fn froxy_configs<A: ToSocketAddrs + std::fmt::Display>(
+    addr: A,
+) -> Result<Vec<FroxyConfig>> {
+    println!("Connecting to froxy {}", addr);
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await?;
+
+    parse(&buf)
+}
+
+/// Asks froxy for its sections until it answers, waiting longer after every failure.
+pub async fn froxy_configs_retrying(addr: &str) -> Vec<FroxyConfig> {
+    let mut backoff = Backoff::default();
+    loop {
+        match froxy_configs(addr).await {
+            Ok(configs) => return configs,
+            Err(e) => {
+                backoff.fail();
+                eprintln!(
+                    "Froxy {} failed: {}, retrying in {:?}",
+                    addr,
+                    e,
+                    backoff.delay()
+                );
+                sleep(backoff.delay()).await;
+            }
+        }
+    }
+}
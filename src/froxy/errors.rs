@@ -0,0 +1,20 @@
+error_chain::error_chain! {
+    errors {
+        Truncated(expected: u64, got: usize) {
+            description("froxy reply is truncated")
+            display("froxy reply is truncated, expected {} bytes, got {}", expected, got)
+        }
+        Trailing(extra: usize) {
+            description("froxy reply has bytes after the last section")
+            display("froxy reply has {} bytes after the last section", extra)
+        }
+        EmptySection(index: usize) {
+            description("froxy section has no pixels")
+            display("section {} has no pixels", index)
+        }
+    }
+    foreign_links {
+        Io(::std::io::Error);
+        Json(::serde_json::error::Error);
+    }
+}
@@ -0,0 +1,11 @@
+//! The froxy protocol, which tells clients the regions of the wall they draw in.
+//! A froxy answers every connection with its sections and closes it.
+
+mod errors;
+pub use errors::*;
+mod wire;
+pub use wire::*;
+mod client;
+pub use client::*;
+mod server;
+pub use server::*;
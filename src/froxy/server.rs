@@ -0,0 +1,51 @@
+use std::{net::SocketAddr, path::Path};
+
+use async_std::{
+    io::WriteExt,
+    net::{TcpListener, ToSocketAddrs},
+    stream::StreamExt,
+};
+
+use super::{check_sections, serialize, FroxyConfig, Result};
+
+/// Reads a region layout, a JSON list of sections like
+/// `[{"x": 0, "y": 0, "width": 64, "height": 32}]`.
+pub async fn load_layout<P: AsRef<Path>>(path: P) -> Result<Vec<FroxyConfig>> {
+    let json = async_std::fs::read_to_string(path.as_ref()).await?;
+    let sections: Vec<FroxyConfig> = serde_json::from_str(&json)?;
+
+    check_sections(&sections)?;
+    Ok(sections)
+}
+
+/// A stand-in for the froxy of the wall, serving a fixed layout.
+pub struct FroxyServer {
+    listener: TcpListener,
+    reply: Vec<u8>,
+}
+
+impl FroxyServer {
+    pub async fn bind<A: ToSocketAddrs>(addr: A, sections: &[FroxyConfig]) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            reply: serialize(sections),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Answers connections until the listener fails.
+    pub async fn run(self) -> Result<()> {
+        let mut incoming = self.listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let mut stream = stream?;
+            // A client going away early only concerns that client
+            if let Err(e) = stream.write_all(&self.reply).await {
+                eprintln!("Froxy client failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
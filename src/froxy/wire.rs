@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ErrorKind, Result};
+
+/// Bytes of the reply before the sections, the big endian section count
+pub const HEADER_LEN: usize = 8;
+/// Bytes per section: big endian width, height, x, y and port
+pub const SECTION_LEN: usize = 16;
+
+/// A region of the wall
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct FroxyConfig {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default)]
+    pub port: u64,
+}
+
+impl FroxyConfig {
+    fn parse(section: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_be_bytes([section[i], section[i + 1]]);
+        let mut port = [0; 8];
+        port.copy_from_slice(&section[8..16]);
+
+        Self {
+            width: u16_at(0),
+            height: u16_at(2),
+            x: u16_at(4),
+            y: u16_at(6),
+            port: u64::from_be_bytes(port),
+        }
+    }
+
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        for v in [self.width, self.height, self.x, self.y] {
+            buf.extend_from_slice(&v.to_be_bytes());
+        }
+        buf.extend_from_slice(&self.port.to_be_bytes());
+    }
+}
+
+/// Parses a complete froxy reply, which has to hold exactly the sections it announces,
+/// none of them empty.
+pub fn parse(buf: &[u8]) -> Result<Vec<FroxyConfig>> {
+    if buf.len() < HEADER_LEN {
+        return Err(ErrorKind::Truncated(HEADER_LEN as u64, buf.len()).into());
+    }
+
+    let mut count = [0; HEADER_LEN];
+    count.copy_from_slice(&buf[..HEADER_LEN]);
+    let count = u64::from_be_bytes(count);
+
+    // Checked before allocating anything, the count can be anything
+    let expected = count
+        .saturating_mul(SECTION_LEN as u64)
+        .saturating_add(HEADER_LEN as u64);
+    if (buf.len() as u64) < expected {
+        return Err(ErrorKind::Truncated(expected, buf.len()).into());
+    }
+    if buf.len() as u64 > expected {
+        return Err(ErrorKind::Trailing(buf.len() - expected as usize).into());
+    }
+
+    let sections: Vec<_> = buf[HEADER_LEN..]
+        .chunks_exact(SECTION_LEN)
+        .map(FroxyConfig::parse)
+        .collect();
+    check_sections(&sections)?;
+    Ok(sections)
+}
+
+/// Sections without pixels can't be drawn in, whether they come from a froxy or a layout.
+pub(super) fn check_sections(sections: &[FroxyConfig]) -> Result<()> {
+    match sections.iter().position(|s| s.width == 0 || s.height == 0) {
+        Some(index) => Err(ErrorKind::EmptySection(index).into()),
+        None => Ok(()),
+    }
+}
+
+/// The reply a froxy sends for `sections`.
+pub fn serialize(sections: &[FroxyConfig]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + SECTION_LEN * sections.len());
+    buf.extend_from_slice(&(sections.len() as u64).to_be_bytes());
+    for section in sections {
+        section.serialize(&mut buf);
+    }
+    buf
+}
@@ -1,11 +1,19 @@
 use std::{error::Error, future::Future};
 
+pub mod backoff;
+pub mod clock;
+pub mod contact;
 pub mod cube;
+pub mod export;
 pub mod framework;
 pub mod francis;
+pub mod froxy;
+pub mod receiver;
+pub mod render;
 pub mod screenshot;
 pub mod shadertoy;
 pub mod util;
+pub mod video;
 
 pub enum Event {
     UpdateArgs(Args),
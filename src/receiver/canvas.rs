@@ -0,0 +1,69 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use image::{Rgba, RgbaImage};
+
+/// A canvas shared between the connections drawing on it and whoever shows it
+pub type SharedCanvas = Arc<Mutex<Canvas>>;
+
+/// The pixels received so far, starting out black
+pub struct Canvas {
+    image: RgbaImage,
+    /// Grows with every pixel drawn, to tell whether the canvas changed
+    generation: u64,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            image: RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+            generation: 0,
+        }
+    }
+
+    pub fn shared(width: u32, height: u32) -> SharedCanvas {
+        Arc::new(Mutex::new(Self::new(width, height)))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        self.image.get_pixel_checked(x, y).map(|p| p.0)
+    }
+
+    /// Blends a colour over the pixel at `x`, `y`, pixels outside the canvas are dropped
+    /// like the wall drops them. Returns whether the pixel was on the canvas.
+    pub fn set(&mut self, x: u32, y: u32, [r, g, b, a]: [u8; 4]) -> bool {
+        let Some(pixel) = self.image.get_pixel_mut_checked(x, y) else {
+            return false;
+        };
+
+        let blend = |src: u8, dst: u8| {
+            ((src as u32 * a as u32 + dst as u32 * (255 - a as u32) + 127) / 255) as u8
+        };
+        let [dr, dg, db, _] = pixel.0;
+        pixel.0 = [blend(r, dr), blend(g, dg), blend(b, db), 255];
+        self.generation += 1;
+        true
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.image.save(path)
+    }
+}
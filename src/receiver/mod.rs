@@ -0,0 +1,9 @@
+//! A local stand-in for the wall. It receives pixels like francis and pixelflut servers do
+//! and keeps them on a canvas, shown in a window or dumped to PNG files.
+
+mod canvas;
+pub use canvas::*;
+mod server;
+pub use server::*;
+mod view;
+pub use view::*;
@@ -0,0 +1,259 @@
+use std::{net::SocketAddr, path::Path, time::Duration};
+
+use async_std::{
+    io::{self, prelude::BufReadExt, BufReader, Cursor, ReadExt, WriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    stream::StreamExt,
+    task,
+};
+
+use super::SharedCanvas;
+use crate::francis::Protocol;
+
+/// Bytes read from a connection at once
+const READ_LEN: usize = 64 * 1024;
+
+/// Receives pixels into a canvas, from any number of connections at a time.
+pub struct Receiver {
+    listener: TcpListener,
+    canvas: SharedCanvas,
+    protocol: Option<Protocol>,
+    packed_alpha: bool,
+}
+
+impl Receiver {
+    /// Listens on `addr`. Without a `protocol` every connection is told apart by how it
+    /// starts, which works for all but the packed protocol. Packed records carry alpha
+    /// when `packed_alpha` is set.
+    pub async fn bind<A: ToSocketAddrs>(
+        addr: A,
+        canvas: SharedCanvas,
+        protocol: Option<Protocol>,
+        packed_alpha: bool,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            canvas,
+            protocol,
+            packed_alpha,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until the listener fails.
+    pub async fn run(self) -> io::Result<()> {
+        let mut incoming = self.listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let stream = stream?;
+            let canvas = self.canvas.clone();
+            let (protocol, packed_alpha) = (self.protocol, self.packed_alpha);
+            task::spawn(async move {
+                let peer = stream.peer_addr();
+                // A misbehaving client only loses its own connection
+                if let Err(e) = receive(stream, canvas, protocol, packed_alpha).await {
+                    eprintln!("Receiving from {:?} failed: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Writes the canvas to `path` every `interval` while it changes. The file is replaced
+/// in one go, so it can be watched while it's written.
+pub async fn dump_png<P: AsRef<Path>>(
+    canvas: SharedCanvas,
+    path: P,
+    interval: Duration,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp.png");
+    let mut dumped = None;
+    loop {
+        let image = {
+            let canvas = canvas.lock().unwrap();
+            if dumped == Some(canvas.generation()) {
+                None
+            } else {
+                dumped = Some(canvas.generation());
+                Some(canvas.image().clone())
+            }
+        };
+
+        if let Some(image) = image {
+            image.save(&tmp).map_err(io::Error::other)?;
+            async_std::fs::rename(&tmp, path).await?;
+        }
+        task::sleep(interval).await;
+    }
+}
+
+async fn receive(
+    mut stream: TcpStream,
+    canvas: SharedCanvas,
+    protocol: Option<Protocol>,
+    packed_alpha: bool,
+) -> io::Result<()> {
+    let mut start = [0; 2];
+    match stream.read_exact(&mut start).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+        Err(e) => return Err(e),
+    }
+
+    let protocol = protocol.unwrap_or_else(|| detect(start));
+    match protocol {
+        Protocol::Binary => records(stream, &start, canvas, 7, francis_record).await,
+        Protocol::Pb => records(stream, &start, canvas, 10, pb_record).await,
+        Protocol::Packed if packed_alpha => records(stream, &start, canvas, 8, packed_record).await,
+        Protocol::Packed => records(stream, &start, canvas, 7, packed_record).await,
+        Protocol::Text => text(stream, &start, canvas).await,
+    }
+}
+
+/// Tells the protocol of a connection from its first two bytes. Pixelflut commands
+/// start with a letter, francis records with the high byte of x, which is far below
+/// the letters on any wall.
+fn detect(start: [u8; 2]) -> Protocol {
+    match &start {
+        b"PB" => Protocol::Pb,
+        [b'P' | b'S' | b'O' | b'H', _] => Protocol::Text,
+        _ => Protocol::Binary,
+    }
+}
+
+type Record = (u32, u32, [u8; 4]);
+
+fn francis_record(r: &[u8]) -> io::Result<Record> {
+    let x = u16::from_be_bytes([r[0], r[1]]);
+    let y = u16::from_be_bytes([r[2], r[3]]);
+    Ok((x as u32, y as u32, [r[4], r[5], r[6], 255]))
+}
+
+fn pb_record(r: &[u8]) -> io::Result<Record> {
+    if &r[..2] != b"PB" {
+        return Err(invalid(format!("PB record starting with {:?}", &r[..2])));
+    }
+    let x = u16::from_le_bytes([r[2], r[3]]);
+    let y = u16::from_le_bytes([r[4], r[5]]);
+    Ok((x as u32, y as u32, [r[6], r[7], r[8], r[9]]))
+}
+
+fn packed_record(r: &[u8]) -> io::Result<Record> {
+    let x = u16::from_le_bytes([r[0], r[1]]);
+    let y = u16::from_le_bytes([r[2], r[3]]);
+    let a = r.get(7).copied().unwrap_or(255);
+    Ok((x as u32, y as u32, [r[4], r[5], r[6], a]))
+}
+
+/// Draws fixed size records until the connection closes, a whole read at a time.
+async fn records(
+    mut stream: TcpStream,
+    start: &[u8],
+    canvas: SharedCanvas,
+    len: usize,
+    decode: fn(&[u8]) -> io::Result<Record>,
+) -> io::Result<()> {
+    let mut buf = start.to_vec();
+    let mut read = vec![0; READ_LEN];
+    loop {
+        let records = buf.len() / len * len;
+        {
+            let mut canvas = canvas.lock().unwrap();
+            for record in buf[..records].chunks_exact(len) {
+                let (x, y, rgba) = decode(record)?;
+                canvas.set(x, y, rgba);
+            }
+        }
+        buf.drain(..records);
+
+        let n = stream.read(&mut read).await?;
+        if n == 0 {
+            if !buf.is_empty() {
+                return Err(invalid(format!(
+                    "connection closed in the middle of a {} byte record",
+                    len
+                )));
+            }
+            return Ok(());
+        }
+        buf.extend_from_slice(&read[..n]);
+    }
+}
+
+/// Answers pixelflut text commands until the connection closes.
+async fn text(stream: TcpStream, start: &[u8], canvas: SharedCanvas) -> io::Result<()> {
+    let mut writer = stream.clone();
+    let mut lines = BufReader::new(Cursor::new(start.to_vec()).chain(stream)).lines();
+    let (mut dx, mut dy) = (0, 0);
+
+    while let Some(line) = lines.next().await {
+        let line = line?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {}
+            ["PX", x, y] => {
+                let (x, y) = (offset(x, dx)?, offset(y, dy)?);
+                let colour = canvas.lock().unwrap().get(x, y);
+                if let Some([r, g, b, _]) = colour {
+                    let reply = format!("PX {} {} {:02x}{:02x}{:02x}\n", x - dx, y - dy, r, g, b);
+                    writer.write_all(reply.as_bytes()).await?;
+                }
+            }
+            ["PX", x, y, colour] => {
+                let (x, y) = (offset(x, dx)?, offset(y, dy)?);
+                let colour = parse_colour(colour)?;
+                canvas.lock().unwrap().set(x, y, colour);
+            }
+            ["SIZE"] => {
+                let (width, height) = {
+                    let canvas = canvas.lock().unwrap();
+                    (canvas.width(), canvas.height())
+                };
+                writer
+                    .write_all(format!("SIZE {} {}\n", width, height).as_bytes())
+                    .await?;
+            }
+            ["OFFSET", x, y] => (dx, dy) = (number(x)?, number(y)?),
+            ["HELP"] => {
+                let help = "HELP PX x y [rrggbb[aa]], SIZE, OFFSET x y\n";
+                writer.write_all(help.as_bytes()).await?;
+            }
+            _ => return Err(invalid(format!("unknown command '{}'", line))),
+        }
+    }
+    Ok(())
+}
+
+fn number(s: &str) -> io::Result<u32> {
+    s.parse()
+        .map_err(|_| invalid(format!("invalid coordinate '{}'", s)))
+}
+
+/// The coordinate `s` moved by the connection's offset
+fn offset(s: &str, by: u32) -> io::Result<u32> {
+    number(s)?
+        .checked_add(by)
+        .ok_or_else(|| invalid(format!("coordinate '{}' is out of range", s)))
+}
+
+/// Parses `rrggbb` or `rrggbbaa`
+fn parse_colour(s: &str) -> io::Result<[u8; 4]> {
+    let error = || invalid(format!("invalid colour '{}'", s));
+    if !s.is_ascii() || (s.len() != 6 && s.len() != 8) {
+        return Err(error());
+    }
+
+    let mut rgba = [0, 0, 0, 255];
+    for (i, c) in rgba.iter_mut().take(s.len() / 2).enumerate() {
+        *c = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| error())?;
+    }
+    Ok(rgba)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,101 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use async_std::task::sleep;
+use futures_util::StreamExt;
+
+use crate::{
+    screenshot::{scrot_with, Ctx, Frame, FrameSettings},
+    Renderable, RenderableConfig,
+};
+
+/// Fixed timestep settings for an offline render.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    /// Time of the first frame in seconds
+    pub start: f32,
+    /// Seconds to render
+    pub duration: f32,
+    /// Hand out frames at `fps` instead of as fast as possible
+    pub realtime: bool,
+    pub settings: FrameSettings,
+}
+
+impl RenderOptions {
+    pub fn frame_count(&self) -> usize {
+        (self.duration * self.fps).round().max(1.0) as usize
+    }
+
+    /// Time of frame `i`, derived from the frame number so rounding errors don't add up.
+    pub fn time(&self, i: usize) -> f32 {
+        self.start + i as f32 / self.fps
+    }
+}
+
+/// Renders every frame with a fixed timestep and hands it to `sink` together with its number.
+pub async fn render<E, F>(
+    ctx: &Ctx,
+    input: E::Input,
+    options: &RenderOptions,
+    mut sink: F,
+) -> Result<(), Box<dyn Error>>
+where
+    E: Renderable + RenderableConfig,
+    F: FnMut(usize, Frame) -> Result<(), Box<dyn Error>>,
+{
+    let mut anim = scrot_with::<E>(
+        ctx,
+        options.width,
+        options.height,
+        input,
+        options.settings,
+    )
+    .await?;
+
+    let times = (0..options.frame_count()).map(|i| options.time(i));
+    let mut frames = std::pin::pin!(anim.stream(ctx, times).enumerate());
+
+    let started = Instant::now();
+    while let Some((i, frame)) = frames.next().await {
+        if options.realtime {
+            // Frames that are late are not dropped, the next ones just don't wait
+            let due = Duration::from_secs_f32(i as f32 / options.fps);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                sleep(wait).await;
+            }
+        }
+
+        sink(i, frame?)?;
+    }
+
+    Ok(())
+}
+
+/// Writes frames as numbered PNG files into a directory.
+pub struct PngSequence {
+    dir: PathBuf,
+}
+
+impl PngSequence {
+    pub fn create(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(format!("frame_{:05}.png", i))
+    }
+
+    pub fn write(&self, i: usize, frame: &Frame) -> Result<(), Box<dyn Error>> {
+        frame.to_image().save(self.path(i))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// How shader output is encoded into the 8 bit frame.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transfer {
+    /// Shader output as is, like shader toy shows it
+    #[default]
+    Linear,
+    /// sRGB encoded, like the window which renders to an sRGB surface
+    Srgb,
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(Transfer::Linear),
+            "srgb" => Ok(Transfer::Srgb),
+            _ => Err(format!("Unknown transfer '{}', expected linear or srgb", s)),
+        }
+    }
+}
+
+pub const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Colour adjustments applied to captured frames, in this order:
+/// the transfer, brightness and contrast, the calibration matrix and the gamma.
+/// The defaults leave the shader output untouched.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ColourPipeline {
+    pub transfer: Transfer,
+    /// Added to every channel, 0 leaves it unchanged
+    pub brightness: f32,
+    /// Scales every channel around 0.5, 1 leaves it unchanged
+    pub contrast: f32,
+    /// Rows are the output red, green and blue, columns the input channels.
+    /// For calibrating a panel, like `[[0.9, 0, 0], [0, 1, 0], [0, 0.05, 0.8]]`
+    pub matrix: [[f32; 3]; 3],
+    /// Exponent applied last, `out = in ^ gamma`. LED panels driven
+    /// linearly look right at around 2.2
+    pub gamma: f32,
+}
+
+impl Default for ColourPipeline {
+    fn default() -> Self {
+        Self {
+            transfer: Transfer::Linear,
+            brightness: 0.0,
+            contrast: 1.0,
+            matrix: IDENTITY,
+            gamma: 1.0,
+        }
+    }
+}
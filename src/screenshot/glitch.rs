@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Glitch effects applied to frames in the post process pass, see `shaders/post.wgsl`.
+/// All of them are off by default. A new random pattern is drawn every frame.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Glitch {
+    /// Share of pixels keeping the colour of the previous frame, as if they got lost
+    pub dropout: f32,
+    /// Share of row bands shifted sideways, tearing the frame
+    pub tearing: f32,
+    /// Output pixels the red and blue channels are shifted apart
+    pub shift: f32,
+    /// Strength of random colour noise added to every pixel
+    pub noise: f32,
+    /// Share of 16 x 16 pixel blocks showing another part of the frame
+    pub blocks: f32,
+}
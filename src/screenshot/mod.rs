@@ -0,0 +1,521 @@
+mod colour;
+pub use colour::*;
+mod glitch;
+pub use glitch::*;
+mod post;
+pub use post::*;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    num::NonZeroU32,
+};
+
+use futures_util::{stream, Stream};
+use wgpu::{util::align_to, BindGroup, Buffer, Texture, TextureView};
+
+use crate::{clock::Clock, Renderable, RenderableConfig};
+
+pub struct Ctx {
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+impl Ctx {
+    pub async fn new<E: RenderableConfig>() -> Self {
+        Self::request::<E>(false)
+            .await
+            .expect("No suitable GPU adapters found on the system!")
+    }
+
+    /// Sets up a headless device. `software` forces a fallback adapter
+    /// (lavapipe, llvmpipe), for machines without a GPU.
+    pub async fn request<E: RenderableConfig>(software: bool) -> Result<Self, Box<dyn Error>> {
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all);
+        let dx12_shader_compiler = wgpu::util::dx12_shader_compiler_from_env().unwrap_or_default();
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            dx12_shader_compiler,
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None, // Some(&surface)
+                force_fallback_adapter: software,
+            })
+            .await
+            .ok_or("No suitable GPU adapters found on the system!")?;
+
+        let adapter_info = adapter.get_info();
+        eprintln!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
+
+        let needed_limits = E::required_limits().using_resolution(adapter.limits());
+
+        let optional_features = E::optional_features();
+        let required_features = E::required_features();
+        let adapter_features = adapter.features();
+
+        let trace_dir = std::env::var("WGPU_TRACE");
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: (optional_features & adapter_features) | required_features,
+                    limits: needed_limits,
+                },
+                trace_dir.ok().as_ref().map(std::path::Path::new),
+            )
+            .await?;
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
+
+/// Bytes per row in readback buffers, wgpu wants rows aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    align_to(width * 4, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+/// How many frames can be in flight by default, see `AnimScrot::set_depth`.
+pub const DEFAULT_DEPTH: usize = 3;
+
+/// A render target per size, with a ring of staging buffers to read it back through,
+/// and the textures scenes are rendered into before post processing.
+#[derive(Default, Debug)]
+struct TextureProvider {
+    textures: HashMap<(u32, u32), (Texture, Vec<Buffer>)>,
+    sources: HashMap<(u32, u32), (Texture, TextureView, BindGroup)>,
+}
+
+/// Everything a single frame is rendered into.
+struct Targets<'a> {
+    scene: &'a TextureView,
+    scene_bind_group: &'a BindGroup,
+    texture: &'a Texture,
+    buffer: &'a Buffer,
+}
+
+impl TextureProvider {
+    /// Creates whatever is missing to render a scene of `render_size`
+    /// into an output of `size` using staging buffer `slot`.
+    fn prepare(
+        &mut self,
+        size: (u32, u32),
+        render_size: (u32, u32),
+        slot: usize,
+        ctx: &Ctx,
+        post: &PostProcess,
+    ) {
+        self.sources.entry(render_size).or_insert_with(|| {
+            let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("scene"),
+                size: wgpu::Extent3d {
+                    width: render_size.0,
+                    height: render_size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Bgra8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = post.bind_group(&ctx.device, &view);
+            (texture, view, bind_group)
+        });
+
+        let (_, buffers) = self.textures.entry(size).or_insert_with(|| {
+            let dst_texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("destination"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Bgra8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            (dst_texture, Vec::new())
+        });
+
+        while buffers.len() <= slot {
+            buffers.push(ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("image map buffer"),
+                size: padded_bytes_per_row(size.0) as u64 * size.1 as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+        }
+    }
+
+    fn targets(&self, size: (u32, u32), render_size: (u32, u32), slot: usize) -> Targets<'_> {
+        let (_, ref scene, ref scene_bind_group) = self.sources[&render_size];
+        let (ref texture, ref buffers) = self.textures[&size];
+        Targets {
+            scene,
+            scene_bind_group,
+            texture,
+            buffer: &buffers[slot],
+        }
+    }
+
+    fn buffer(&self, size: (u32, u32), slot: usize) -> &Buffer {
+        &self.textures[&size].1[slot]
+    }
+}
+
+/// A frame that is rendered and being copied into staging buffer `slot`.
+struct InFlight {
+    size: (u32, u32),
+    slot: usize,
+    submission: wgpu::SubmissionIndex,
+    mapped: async_channel::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+pub struct AnimScrot<E> {
+    width: u32,
+    height: u32,
+    example: E,
+    post: PostProcess,
+    settings: FrameSettings,
+    clock: Clock,
+    texture_provider: TextureProvider,
+    in_flight: VecDeque<InFlight>,
+    depth: usize,
+    next_slot: usize,
+    /// Size of the last frame, which the output texture of that size still holds.
+    /// Dropped pixels keep their colour from it, `None` when there's no such frame
+    previous: Option<(u32, u32)>,
+}
+
+/// Tightly packed `Bgra8Unorm` pixels
+#[derive(Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub buffer: Vec<u8>,
+}
+
+impl Frame {
+    pub fn to_image(&self) -> image::RgbaImage {
+        let mut rgba = self.buffer.clone();
+        rgba.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+
+        image::RgbaImage::from_raw(self.width, self.height, rgba)
+            .expect("frame buffer matches its size")
+    }
+}
+
+pub async fn scrot_new<E: Renderable + RenderableConfig>(
+    ctx: &Ctx,
+    width: u32,
+    height: u32,
+    input: E::Input,
+) -> Result<AnimScrot<E>, Box<dyn Error>> {
+    scrot_with(ctx, width, height, input, FrameSettings::default()).await
+}
+
+/// Like `scrot_new`, with `settings` used for every frame that doesn't pass its own.
+pub async fn scrot_with<E: Renderable + RenderableConfig>(
+    ctx: &Ctx,
+    width: u32,
+    height: u32,
+    input: E::Input,
+    settings: FrameSettings,
+) -> Result<AnimScrot<E>, Box<dyn Error>> {
+    let (render_width, render_height) = settings.render_size((width, height));
+    let example = E::init(
+        &wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8Unorm,
+            width: render_width,
+            height: render_height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![
+                wgpu::TextureFormat::Bgra8Unorm,
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+            ],
+        },
+        &ctx.adapter,
+        &ctx.device,
+        &ctx.queue,
+        input,
+    )
+    .await?;
+
+    let texture_provider = TextureProvider::default();
+    let post = PostProcess::new(&ctx.device, wgpu::TextureFormat::Bgra8Unorm);
+
+    Ok(AnimScrot {
+        width,
+        height,
+        example,
+        post,
+        settings,
+        clock: Clock::default(),
+        texture_provider,
+        in_flight: VecDeque::new(),
+        depth: DEFAULT_DEPTH,
+        next_slot: 0,
+        previous: None,
+    })
+}
+
+impl<E> AnimScrot<E> {
+    /// Clock used by `next_frame`, follows the wall clock unless it's replaced.
+    pub fn clock(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Sets how many frames can be in flight, frames that are already in flight are discarded.
+    pub fn set_depth(&mut self, ctx: &Ctx, depth: usize) {
+        self.discard(ctx);
+        self.depth = depth.max(1);
+        self.next_slot = 0;
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Forgets the last frame, so the next one drops no pixels. For when the frames
+    /// are meant for another target, whose previous frame this isn't.
+    pub fn forget_previous(&mut self) {
+        self.previous = None;
+    }
+
+    /// Waits for the oldest frame in flight and reads it back,
+    /// `None` when there are no frames in flight.
+    /// Reading back fails when the GPU can't map the buffer, for example after losing the device.
+    pub async fn receive(&mut self, ctx: &Ctx) -> Option<Result<Frame, wgpu::BufferAsyncError>> {
+        let InFlight {
+            size,
+            slot,
+            submission,
+            mapped,
+        } = self.in_flight.pop_front()?;
+
+        ctx.device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        // A dropped callback never mapped the buffer either
+        if let Err(e) = mapped.recv().await.unwrap_or(Err(wgpu::BufferAsyncError)) {
+            return Some(Err(e));
+        }
+
+        let buf = self.texture_provider.buffer(size, slot);
+        let buffer = {
+            let padded = buf.slice(..).get_mapped_range();
+            let row = size.0 as usize * 4;
+            padded
+                .chunks_exact(padded_bytes_per_row(size.0) as usize)
+                .flat_map(|padded_row| &padded_row[..row])
+                .copied()
+                .collect()
+        };
+        buf.unmap();
+
+        Some(Ok(Frame {
+            width: size.0,
+            height: size.1,
+            buffer,
+        }))
+    }
+
+    /// Drops every frame in flight, for example when the frames are meant for a target that changed.
+    pub fn discard(&mut self, ctx: &Ctx) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+
+        ctx.device.poll(wgpu::Maintain::Wait);
+        for InFlight { size, slot, .. } in self.in_flight.drain(..) {
+            self.texture_provider.buffer(size, slot).unmap();
+        }
+    }
+}
+
+impl<E: Renderable> AnimScrot<E> {
+    /// Renders a frame and starts reading it back, without waiting for the GPU.
+    /// At most `depth` frames can be in flight, `receive` them to make room.
+    pub fn submit(&mut self, ctx: &Ctx, time: f32, size: Option<(u32, u32)>) {
+        let settings = self.settings;
+        self.submit_with(ctx, time, size, &settings);
+    }
+
+    /// Like `submit`, with its own supersampling and colour settings.
+    pub fn submit_with(
+        &mut self,
+        ctx: &Ctx,
+        time: f32,
+        size: Option<(u32, u32)>,
+        settings: &FrameSettings,
+    ) {
+        assert!(
+            self.in_flight.len() < self.depth,
+            "all {} staging buffers are in flight",
+            self.depth
+        );
+
+        let size = size.unwrap_or((self.width, self.height));
+        let render_size = settings.render_size(size);
+
+        // Without a previous frame dropped pixels would be left black, or keep a frame
+        // meant for somewhere else
+        let fresh = self.previous != Some(size);
+        self.previous = Some(size);
+        let mut settings = *settings;
+        if fresh {
+            settings.glitch.dropout = 0.0;
+        }
+
+        self.example
+            .update(time, render_size, &ctx.device, &ctx.queue);
+
+        let slot = self.next_slot;
+        self.next_slot = (self.next_slot + 1) % self.depth;
+
+        self.texture_provider
+            .prepare(size, render_size, slot, ctx, &self.post);
+        let targets = self.texture_provider.targets(size, render_size, slot);
+
+        self.example.render(targets.scene, &ctx.device, &ctx.queue);
+
+        let mut cmd_buf = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let view = targets
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.post.run(
+            &ctx.queue,
+            &mut cmd_buf,
+            targets.scene_bind_group,
+            &view,
+            fresh,
+            &settings,
+        );
+
+        cmd_buf.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: targets.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: targets.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row(size.0)),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let submission = ctx.queue.submit(Some(cmd_buf.finish()));
+
+        let (tx, mapped) = async_channel::bounded(1);
+        targets
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.try_send(result);
+            });
+
+        self.in_flight.push_back(InFlight {
+            size,
+            slot,
+            submission,
+            mapped,
+        });
+    }
+
+    /// Renders a single frame and waits for it, frames already in flight are discarded.
+    pub async fn frame(
+        &mut self,
+        ctx: &Ctx,
+        time: f32,
+        size: Option<(u32, u32)>,
+    ) -> Result<Frame, wgpu::BufferAsyncError> {
+        let settings = self.settings;
+        self.frame_with(ctx, time, size, &settings).await
+    }
+
+    /// Like `frame`, with its own supersampling and colour settings.
+    pub async fn frame_with(
+        &mut self,
+        ctx: &Ctx,
+        time: f32,
+        size: Option<(u32, u32)>,
+        settings: &FrameSettings,
+    ) -> Result<Frame, wgpu::BufferAsyncError> {
+        self.discard(ctx);
+        self.submit_with(ctx, time, size, settings);
+        self.receive(ctx).await.expect("a frame was just submitted")
+    }
+
+    /// Renders the frame at the next tick of the clock.
+    pub async fn next_frame(
+        &mut self,
+        ctx: &Ctx,
+        size: Option<(u32, u32)>,
+    ) -> Result<Frame, wgpu::BufferAsyncError> {
+        let time = self.clock.tick();
+        self.frame(ctx, time, size).await
+    }
+
+    /// Renders a frame for every time, keeping up to `depth` frames in flight
+    /// so the GPU renders the next frames while earlier ones are read back.
+    pub fn stream<'a, I>(
+        &'a mut self,
+        ctx: &'a Ctx,
+        times: I,
+    ) -> impl Stream<Item = Result<Frame, wgpu::BufferAsyncError>> + 'a
+    where
+        I: IntoIterator<Item = f32>,
+        I::IntoIter: 'a,
+    {
+        stream::unfold(
+            (self, times.into_iter()),
+            move |(anim, mut times)| async move {
+                while anim.in_flight() < anim.depth() {
+                    match times.next() {
+                        Some(time) => anim.submit(ctx, time, None),
+                        None => break,
+                    }
+                }
+
+                let frame = anim.receive(ctx).await?;
+                Some((frame, (anim, times)))
+            },
+        )
+    }
+}
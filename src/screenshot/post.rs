@@ -0,0 +1,257 @@
+use std::{borrow::Cow, str::FromStr};
+
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+
+use super::{ColourPipeline, Glitch, Transfer};
+
+/// How a supersampled frame is filtered down to the output size.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownsampleFilter {
+    /// Average of the pixels covering an output pixel
+    #[default]
+    Box,
+    /// Sharper, but can ring around hard edges
+    Lanczos,
+}
+
+impl FromStr for DownsampleFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "box" => Ok(DownsampleFilter::Box),
+            "lanczos" => Ok(DownsampleFilter::Lanczos),
+            _ => Err(format!("Unknown filter '{}', expected box or lanczos", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct FrameSettings {
+    /// Render at this multiple of the output size, 1 renders at the output size
+    pub supersample: u32,
+    pub filter: DownsampleFilter,
+    pub colour: ColourPipeline,
+    /// Set per frame by the francis handler and the command line, not read from playlists
+    #[serde(skip)]
+    pub glitch: Glitch,
+}
+
+impl Default for FrameSettings {
+    fn default() -> Self {
+        Self {
+            supersample: 1,
+            filter: DownsampleFilter::Box,
+            colour: ColourPipeline::default(),
+            glitch: Glitch::default(),
+        }
+    }
+}
+
+impl FrameSettings {
+    fn factor(&self) -> u32 {
+        self.supersample.max(1)
+    }
+
+    /// Size the scene is rendered at for an output of `size`.
+    pub fn render_size(&self, size: (u32, u32)) -> (u32, u32) {
+        (size.0 * self.factor(), size.1 * self.factor())
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Params {
+    factor: u32,
+    mode: u32,
+    transfer: u32,
+    gamma: f32,
+    brightness: f32,
+    contrast: f32,
+    _pad: [u32; 2],
+    matrix: [[f32; 4]; 3],
+    dropout: f32,
+    tearing: f32,
+    shift: f32,
+    noise: f32,
+    blocks: f32,
+    seed: u32,
+    _pad2: [u32; 2],
+}
+
+// Implemented by hand, the derive leaves a dead check function per field behind.
+// SAFETY: every field is a u32 or f32 (array), zero is valid for all of them
+// and the explicit padding leaves no implicit padding bytes, as the size shows.
+const _: () = assert!(std::mem::size_of::<Params>() == 112);
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// The pass between the scene and readback, see `shaders/post.wgsl`.
+pub(super) struct PostProcess {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    params: wgpu::Buffer,
+    /// Frames run so far, seeds the glitch patterns
+    frames: u32,
+}
+
+impl PostProcess {
+    pub(super) fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post process"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post process"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("post process"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../shaders/post.wgsl"
+            ))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("post process"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post process params"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            layout,
+            params,
+            frames: 0,
+        }
+    }
+
+    pub(super) fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post process"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Records the pass, the parameters are written right away so
+    /// they apply to the next submission. `target` keeps its previous
+    /// contents where the glitch drops pixels, unless it's `fresh`.
+    pub(super) fn run(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+        fresh: bool,
+        settings: &FrameSettings,
+    ) {
+        let colour = &settings.colour;
+        let glitch = &settings.glitch;
+        self.frames = self.frames.wrapping_add(1);
+        let params = Params {
+            factor: settings.factor(),
+            mode: match settings.filter {
+                DownsampleFilter::Box => 0,
+                DownsampleFilter::Lanczos => 1,
+            },
+            transfer: match colour.transfer {
+                Transfer::Linear => 0,
+                Transfer::Srgb => 1,
+            },
+            gamma: colour.gamma,
+            brightness: colour.brightness,
+            contrast: colour.contrast,
+            _pad: [0; 2],
+            matrix: colour.matrix.map(|[r, g, b]| [r, g, b, 0.0]),
+            dropout: glitch.dropout,
+            tearing: glitch.tearing,
+            shift: glitch.shift,
+            noise: glitch.noise,
+            blocks: glitch.blocks,
+            seed: self.frames,
+            _pad2: [0; 2],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("post process"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if fresh {
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, source, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
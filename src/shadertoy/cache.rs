@@ -0,0 +1,214 @@
+// Resource cache for the shader toy client.
+// Every cached file is tracked in `manifest.json` in the cache root, together with
+// where it came from, how big it is, when it was fetched and a hash of its contents.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::client::write_atomic;
+
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    /// Url the resource was fetched from, without the api key
+    pub url: String,
+    pub size: u64,
+    /// Seconds since the unix epoch
+    pub fetched: u64,
+    /// Hex encoded sha256 of the contents
+    pub hash: String,
+}
+
+impl CacheEntry {
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.fetched))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Missing,
+    SizeMismatch { expected: u64, found: u64 },
+    HashMismatch,
+}
+
+pub struct Cache {
+    root: PathBuf,
+    manifest: Mutex<Manifest>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Key of a resource in the manifest, relative to the cache root.
+fn key(resource: &str) -> String {
+    resource.trim_start_matches('/').to_string()
+}
+
+impl Cache {
+    /// Opens the cache at `root`, a missing or broken manifest starts out empty.
+    pub fn open(root: impl Into<PathBuf>) -> Cache {
+        let root = root.into();
+        let manifest = std::fs::read(root.join(MANIFEST))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        Cache {
+            root,
+            manifest: Mutex::new(manifest),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, resource: &str) -> PathBuf {
+        self.root.join(key(resource))
+    }
+
+    pub fn entries(&self) -> Vec<(String, CacheEntry)> {
+        let manifest = self.manifest.lock().unwrap();
+        manifest
+            .entries
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    pub fn contains(&self, resource: &str) -> bool {
+        self.manifest
+            .lock()
+            .unwrap()
+            .entries
+            .contains_key(&key(resource))
+    }
+
+    /// Reads a cached resource. Files the manifest doesn't track are ignored,
+    /// so everything served was written by `put` and shows up in `list`, `verify` and `prune`.
+    pub async fn get(&self, resource: &str) -> Option<Vec<u8>> {
+        if !self.contains(resource) {
+            return None;
+        }
+        async_std::fs::read(self.path(resource)).await.ok()
+    }
+
+    pub async fn put(&self, resource: &str, url: &str, bytes: &[u8]) -> std::io::Result<()> {
+        write_atomic(&self.path(resource), bytes).await?;
+
+        let entry = CacheEntry {
+            url: url.to_string(),
+            size: bytes.len() as u64,
+            fetched: now(),
+            hash: content_hash(bytes),
+        };
+        self.manifest
+            .lock()
+            .unwrap()
+            .entries
+            .insert(key(resource), entry);
+
+        self.save().await
+    }
+
+    async fn save(&self) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(&*self.manifest.lock().unwrap())?;
+        write_atomic(&self.root.join(MANIFEST), &json).await
+    }
+
+    /// Checks every entry in the manifest against the file on disk.
+    pub async fn verify(&self) -> Vec<(String, Problem)> {
+        let mut problems = Vec::new();
+
+        for (key, entry) in self.entries() {
+            let problem = match async_std::fs::read(self.root.join(&key)).await {
+                Err(_) => Some(Problem::Missing),
+                Ok(bytes) if bytes.len() as u64 != entry.size => Some(Problem::SizeMismatch {
+                    expected: entry.size,
+                    found: bytes.len() as u64,
+                }),
+                Ok(bytes) if content_hash(&bytes) != entry.hash => Some(Problem::HashMismatch),
+                Ok(_) => None,
+            };
+
+            if let Some(problem) = problem {
+                problems.push((key, problem));
+            }
+        }
+
+        problems
+    }
+
+    /// Removes an entry and its file.
+    pub async fn remove(&self, resource: &str) -> std::io::Result<()> {
+        self.forget(resource).await?;
+        self.save().await
+    }
+
+    /// Removes an entry and its file without saving the manifest.
+    async fn forget(&self, resource: &str) -> std::io::Result<()> {
+        match async_std::fs::remove_file(self.path(resource)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        self.manifest.lock().unwrap().entries.remove(&key(resource));
+        Ok(())
+    }
+
+    /// Removes entries older than `max_age`, then the oldest entries until
+    /// the cache is at most `max_size` bytes. Returns the removed keys.
+    pub async fn prune(
+        &self,
+        max_age: Option<Duration>,
+        max_size: Option<u64>,
+    ) -> std::io::Result<Vec<String>> {
+        let mut entries = self.entries();
+        entries.sort_by_key(|(_, e)| e.fetched);
+
+        let mut total: u64 = entries.iter().map(|(_, e)| e.size).sum();
+        let mut removed = Vec::new();
+
+        for (key, entry) in entries {
+            let too_old = max_age.map(|age| entry.age() > age).unwrap_or(false);
+            let too_big = max_size.map(|size| total > size).unwrap_or(false);
+
+            if too_old || too_big {
+                if let Err(e) = self.forget(&key).await {
+                    // Keep what was removed so far out of the manifest
+                    self.save().await?;
+                    return Err(e);
+                }
+                total -= entry.size;
+                removed.push(key);
+            }
+        }
+
+        if !removed.is_empty() {
+            self.save().await?;
+        }
+        Ok(removed)
+    }
+}
@@ -1,3 +1,4 @@
+use super::cache::Cache;
 use super::errors::*;
 use super::types::*;
 use super::util::InputType;
@@ -7,8 +8,11 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std;
 use std::io::Cursor;
-use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum SearchSortOrder {
@@ -40,10 +44,45 @@ pub struct SearchParams<'a> {
     pub filters: Vec<SearchFilter>,
 }
 
+/// Connection and caching settings for `Client`.
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Where the API lives, can point to a local mirror or test server.
+    pub base_url: String,
+    /// Directory that downloaded resources are cached in.
+    pub cache_root: PathBuf,
+    /// Timeout for a whole request, including reading the body.
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// How many times a failed request is retried.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every next retry.
+    pub backoff: Duration,
+    /// Only use cached resources, fail instead of going to the network.
+    pub offline: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            base_url: "https://www.shadertoy.com".into(),
+            cache_root: "cache".into(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            offline: false,
+        }
+    }
+}
+
 /// Client for issuing queries against the Shadertoy API and database
+#[derive(Clone)]
 pub struct Client {
     pub api_key: String,
     pub rest_client: reqwest::Client,
+    pub options: ClientOptions,
+    pub cache: Arc<Cache>,
 }
 
 impl FromStr for SearchSortOrder {
@@ -81,15 +120,71 @@ impl Client {
     /// Create a new client.
     /// This requires sending in an API key, one can generate one on https://www.shadertoy.com/profile
     pub fn new(api_key: &str) -> Client {
-        Client {
+        Self::with_options(api_key, ClientOptions::default())
+            .expect("Could not create the http client")
+    }
+
+    pub fn with_options(api_key: &str, options: ClientOptions) -> Result<Client> {
+        let rest_client = reqwest::Client::builder()
+            .timeout(options.timeout)
+            .connect_timeout(options.connect_timeout)
+            .build()?;
+
+        Ok(Client {
             api_key: api_key.to_string(),
-            rest_client: reqwest::Client::new(),
+            rest_client,
+            cache: Arc::new(Cache::open(&options.cache_root)),
+            options,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.options.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// Gets `url` with the api key added, retrying with exponential backoff on timeouts,
+    /// connection failures and server errors. Client errors (4xx) are returned immediately.
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        if self.options.offline {
+            bail!(ErrorKind::Offline(url.to_string()));
+        }
+
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let keyed = format!("{}{}key={}", url, separator, self.api_key);
+
+        let mut attempt = 0;
+        loop {
+            // The url contains the api key, keep it out of the logs
+            let result = async {
+                let response = self.rest_client.get(&keyed).send().await?;
+                let response = response.error_for_status()?;
+                response.bytes().await
+            }
+            .await
+            .map_err(|e| e.without_url());
+
+            match result {
+                Ok(bytes) => return Ok(bytes.to_vec()),
+                Err(e) if attempt < self.options.retries && is_retryable(&e) => {
+                    let wait = self.options.backoff * 2u32.pow(attempt.min(16));
+                    eprintln!("Request failed ({}), retrying in {:?}", e, wait);
+                    async_std::task::sleep(wait).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
         }
     }
 
-    pub async fn search(&self, params: &SearchParams<'_>) -> Result<Vec<String>> {
-        let query_str = format!(
-            "https://www.shadertoy.com/api/v1/shaders{}?sort={}&{}key={}",
+    /// Url of the search api for `params`, without the api key.
+    pub fn search_url(&self, params: &SearchParams<'_>) -> String {
+        format!(
+            "{}{}?sort={}{}",
+            self.url("api/v1/shaders"),
             if params.string.is_empty() {
                 "".to_string()
             } else {
@@ -99,21 +194,16 @@ impl Client {
             params
                 .filters
                 .iter()
-                .map(|f| format!("filter={:?}&", f).to_lowercase())
+                .map(|f| format!("&filter={:?}", f).to_lowercase())
                 .collect::<String>(),
-            self.api_key
-        );
+        )
+    }
 
-        let json_str = self
-            .rest_client
-            .get(&query_str)
-            .send()
-            .await?
-            .text()
-            .await?;
+    pub async fn search(&self, params: &SearchParams<'_>) -> Result<Vec<String>> {
+        let query_str = self.search_url(params);
+        let json_str = String::from_utf8_lossy(&self.fetch(&query_str).await?).into_owned();
 
         #[derive(Serialize, Deserialize, Debug)]
-        #[serde(deny_unknown_fields)]
         struct SearchResult {
             #[serde(default)]
             #[serde(rename = "Error")]
@@ -142,70 +232,100 @@ impl Client {
     }
 
     /// Retrives a shader given an id.
+    /// Shaders are always fetched fresh, except in offline mode where the cached copy is used.
     pub async fn get_shader(&self, shader_id: &str, save: Option<&str>) -> Result<Shader> {
-        let json = self
-            .rest_client
-            .get(&format!(
-                "https://www.shadertoy.com/api/v1/shaders/{}?key={}",
-                shader_id, self.api_key
-            ))
-            .send()
-            .await?
-            .json::<ShaderRoot>()
-            .await?;
+        let resource = format!("api/v1/shaders/{}.json", shader_id);
+        let url = format!("{}/{}", self.url("api/v1/shaders"), shader_id);
+
+        let cached = if self.options.offline {
+            self.cache.get(&resource).await
+        } else {
+            None
+        };
+        let body = match cached {
+            Some(body) => body,
+            None => self.fetch(&url).await?,
+        };
 
         #[derive(Serialize, Deserialize, Debug)]
-        #[serde(deny_unknown_fields)]
         struct ShaderRoot {
             #[serde(default)]
             #[serde(rename = "Error")]
             error: String,
 
             #[serde(rename = "Shader")]
-            shader: Shader,
+            shader: Option<Shader>,
         }
 
+        let json: ShaderRoot = serde_json::from_slice(&body)
+            .chain_err(|| "JSON parsing of Shadertoy shader failed")?;
+
         if !json.error.is_empty() {
             bail!("Shadertoy REST shader query returned error: {}", json.error);
         }
 
+        let shader = match json.shader {
+            Some(shader) => shader,
+            None => bail!("Shadertoy REST shader query returned no shader"),
+        };
+
+        if !self.options.offline {
+            if let Err(e) = self.cache.put(&resource, &url, &body).await {
+                eprintln!("Could not cache shader {:?}", e);
+            }
+        }
+
         if let Some(p) = save {
-            let st = serde_json::to_vec_pretty(&json.shader).unwrap();
-            let mut f = std::fs::File::create(p).unwrap();
-            f.write_all(&st).unwrap();
+            let st = serde_json::to_vec_pretty(&shader)?;
+            write_atomic(Path::new(p), &st)
+                .await
+                .chain_err(|| format!("Could not save shader to {}", p))?;
         }
 
-        Ok(json.shader)
+        Ok(shader)
     }
 
     pub async fn get_resource(&self, resource: &str) -> Result<Vec<u8>> {
-        let local = format!("cache{}", resource);
-
-        if let Ok(x) = async_std::fs::read(&local).await {
-            println!("Got resource from cache");
+        if let Some(x) = self.cache.get(resource).await {
+            eprintln!("Got resource from cache");
             Ok(x)
         } else {
-            let data = self
-                .rest_client
-                .get(&format!(
-                    "https://www.shadertoy.com/{}?key={}",
-                    resource, self.api_key
-                ))
-                .send()
-                .await?
-                .bytes()
-                .await?;
-
-            let bytes = data.to_vec();
-
-            if let Err(e) = async_std::fs::write(local, &bytes).await {
-                println!("Could not cache file {:?}", e);
+            let url = self.url(resource);
+            let bytes = self.fetch(&url).await?;
+
+            if let Err(e) = self.cache.put(resource, &url, &bytes).await {
+                eprintln!("Could not cache file {:?}", e);
             }
 
             Ok(bytes)
         }
     }
 
+    /// Downloads every texture `shader` uses into the cache.
+    /// Returns how many resources were downloaded, resources that were cached already don't count.
+    pub async fn prefetch(&self, shader: &Shader) -> Result<usize> {
+        let mut fetched = 0;
+
+        for pass in &shader.renderpass {
+            for input in &pass.inputs {
+                let input_type = match input.ctype {
+                    ChannelType::Texture => InputType::D2,
+                    ChannelType::Cubemap => InputType::Cube,
+                    _ => continue,
+                };
+
+                for resource in resources(&input.src, input_type) {
+                    if !self.cache.contains(&resource) {
+                        self.get_resource(&resource).await?;
+                        fetched += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(fetched)
+    }
+
     pub async fn get_png(
         &self,
         resource: &str,
@@ -213,34 +333,76 @@ impl Client {
     ) -> Result<(Vec<u8>, (u32, u32))> {
         use image::io::Reader as ImageReader;
 
-        let bytes = self.get_resource(resource).await?;
-        let img2 = ImageReader::new(Cursor::new(bytes))
-            .with_guessed_format()?
-            .decode()?;
-
-        let size = (img2.width(), img2.height());
-        let mut raw = img2.into_rgba8().into_raw();
+        let mut raw = Vec::new();
+        let mut size = (0, 0);
 
-        if input_type.is_cube() {
-            let dot_idx = resource.rfind('.').unwrap();
-            let (start, end) = resource.split_at(dot_idx);
-            for i in 1..6 {
-                println!("Getting more cube things {}", i);
-
-                let url = format!("{}_{}{}", start, i, end);
+        for (i, resource) in resources(resource, input_type).iter().enumerate() {
+            if i > 0 {
+                eprintln!("Getting more cube things {}", i);
+            }
 
-                let bytes = self.get_resource(&url).await?;
-                let img2 = ImageReader::new(Cursor::new(bytes))
-                    .with_guessed_format()?
-                    .decode()?;
+            let bytes = self.get_resource(resource).await?;
+            let img2 = ImageReader::new(Cursor::new(bytes))
+                .with_guessed_format()?
+                .decode()?;
 
-                raw.extend_from_slice(&img2.into_rgba8().as_raw());
+            if i == 0 {
+                size = (img2.width(), img2.height());
             }
+            raw.extend_from_slice(img2.into_rgba8().as_raw());
         }
+
         rgba_to_bgra(&mut raw);
         Ok((raw, size))
     }
 }
+
+/// All files that make up a texture input, cubemaps are stored as 6 files.
+fn resources(resource: &str, input_type: InputType) -> Vec<String> {
+    let mut out = vec![resource.to_string()];
+
+    if input_type.is_cube() {
+        if let Some(dot_idx) = resource.rfind('.') {
+            let (start, end) = resource.split_at(dot_idx);
+            out.extend((1..6).map(|i| format!("{}_{}{}", start, i, end)));
+        }
+    }
+
+    out
+}
+
+fn is_retryable(e: &reqwest::Error) -> bool {
+    if let Some(status) = e.status() {
+        return status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    }
+    e.is_timeout() || e.is_connect() || e.is_body() || e.is_request()
+}
+
+/// Writes `bytes` next to `path` and renames it into place,
+/// so readers never see a half written file.
+pub async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    // Every write gets its own temporary file, concurrent writes of a path would clobber a shared one
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+
+    if let Some(dir) = path.parent() {
+        async_std::fs::create_dir_all(dir).await?;
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    async_std::fs::write(&tmp, bytes).await?;
+    if let Err(e) = async_std::fs::rename(&tmp, path).await {
+        let _ = async_std::fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
 fn rgba_to_bgra(vec: &mut Vec<u8>) {
     for i in (0..vec.len()).step_by(4) {
         let tmp = vec[i];
@@ -1,5 +1,15 @@
 
 error_chain::error_chain! {
+    errors {
+        Validation(t: String) {
+            description("invalid shader json")
+            display("invalid shader json: {}", t)
+        }
+        Offline(t: String) {
+            description("resource not cached while offline")
+            display("not cached while offline: '{}'", t)
+        }
+    }
     foreign_links {
         Fmt(::std::fmt::Error);
         Io(::std::io::Error);
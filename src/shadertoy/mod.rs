@@ -1,11 +1,17 @@
 mod types;
 pub use types::*;
+mod cache;
+pub use cache::*;
 mod client;
 pub use client::*;
 mod errors;
 pub use errors::*;
 mod pipeline;
 pub use pipeline::*;
+mod program;
+pub use program::*;
+mod search;
+pub use search::*;
 
 mod util;
 
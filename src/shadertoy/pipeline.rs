@@ -6,7 +6,10 @@ use nanorand::{Rng, WyRand};
 use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, RenderPipeline, Texture};
 
 use crate::{
-    shadertoy::{Client, RenderPassInput},
+    shadertoy::{
+        Channel, ChannelSource, Client, Filter, ParseMode, Pass, PassOutput, PassType, Program,
+        SamplerDesc, Wrap,
+    },
     Renderable, RenderableConfig,
 };
 
@@ -75,7 +78,7 @@ struct PipelineBuilder<'a> {
     samplers_made: usize,
     inner_text: String,
 
-    pass: &'a super::RenderPass,
+    pass: &'a Pass,
     name: &'a str,
     index: usize,
 }
@@ -106,7 +109,12 @@ layout(set = {binding}, binding = 1) uniform sampler u_sampler_{channel};
     )
 }
 
-fn to_wgsl(source: &str, stage: naga::ShaderStage, name: &str, index: usize) -> String {
+fn to_wgsl(
+    source: &str,
+    stage: naga::ShaderStage,
+    name: &str,
+    index: usize,
+) -> Result<String, Box<dyn Error>> {
     use naga::back::wgsl::*;
     use naga::front::glsl::*;
     use naga::valid::*;
@@ -115,7 +123,13 @@ fn to_wgsl(source: &str, stage: naga::ShaderStage, name: &str, index: usize) ->
     let options = Options::from(stage);
     let glsl = match parser.parse(&options, &source) {
         Ok(x) => x,
-        Err(_) => panic!("invalid frag shader!"),
+        Err(errors) => {
+            let errors: Vec<_> = errors
+                .iter()
+                .map(|e| format!("{} at {:?}", e.kind, e.meta.location(source)))
+                .collect();
+            return Err(format!("Invalid {:?} shader: {}", stage, errors.join(", ")).into());
+        }
     };
 
     let mut validator = Validator::new(ValidationFlags::empty(), Capabilities::empty());
@@ -127,16 +141,13 @@ fn to_wgsl(source: &str, stage: naga::ShaderStage, name: &str, index: usize) ->
                 eprintln!(" at {:?} {}", span.location(&source), ctx);
             }
 
-            panic!();
+            return Err(format!("Invalid {:?} shader: {}", stage, r.as_inner()).into());
         }
     };
 
     let cursor = String::new();
     let mut writer = Writer::new(cursor, WriterFlags::EXPLICIT_TYPES);
-    let _ = match writer.write(&glsl, &entry) {
-        Ok(s) => s,
-        Err(e) => panic!("{}", e),
-    };
+    writer.write(&glsl, &entry)?;
 
     // let n = if stage == naga::ShaderStage::Vertex {
     //     "vertex"
@@ -148,7 +159,7 @@ fn to_wgsl(source: &str, stage: naga::ShaderStage, name: &str, index: usize) ->
     let source = writer.finish();
     // f.write_all(source.as_bytes()).unwrap();
 
-    source
+    Ok(source)
 }
 
 impl<'a> PipelineBuilder<'a> {
@@ -156,7 +167,7 @@ impl<'a> PipelineBuilder<'a> {
         common: &'a PipelineBuilderCommon<'a>,
         uniform: &'a mut Uniform,
         textures: &'a mut HashMap<u64, Texture>,
-        pass: &'a super::RenderPass,
+        pass: &'a Pass,
         name: &'a str,
         index: usize,
     ) -> Self {
@@ -179,7 +190,11 @@ impl<'a> PipelineBuilder<'a> {
         self.samplers_made += 1;
     }
 
-    pub fn build<'b>(self, layouts: Layouts<'b>, common_code: &str) -> RenderPass {
+    pub async fn build<'b>(
+        mut self,
+        layouts: Layouts<'b>,
+        common_code: &str,
+    ) -> Result<RenderPass, Box<dyn Error>> {
         let mut bind_group_refs: Vec<_> = vec![layouts.uniform_layout];
         bind_group_refs.extend(self.bind_group_layouts.iter());
 
@@ -205,28 +220,25 @@ impl<'a> PipelineBuilder<'a> {
         //     std::fs::File::create(format!("tmp/{}_source_{}.glsl", self.name, self.index)).unwrap();
         // file.write_all(source.as_bytes()).unwrap();
 
+        let frag_source = to_wgsl(&source, naga::ShaderStage::Fragment, self.name, self.index)
+            .map_err(|e| format!("Pass '{}': {}", self.pass.name, e))?;
+        let vertex_source = to_wgsl(VERTEX, naga::ShaderStage::Vertex, self.name, self.index)?;
+
+        // Report invalid shaders as errors instead of panicking in wgpu's error handler
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
         let frag_shader = self
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("fragment shader"),
-                source: wgpu::ShaderSource::Wgsl(Cow::Owned(to_wgsl(
-                    &source,
-                    naga::ShaderStage::Fragment,
-                    self.name,
-                    self.index,
-                ))),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(frag_source)),
             });
 
         let vertex_shader = self
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("vertex shader"),
-                source: wgpu::ShaderSource::Wgsl(Cow::Owned(to_wgsl(
-                    VERTEX,
-                    naga::ShaderStage::Vertex,
-                    self.name,
-                    self.index,
-                ))),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(vertex_source)),
             });
 
         let pipeline = self
@@ -253,37 +265,43 @@ impl<'a> PipelineBuilder<'a> {
                 multiview: None,
             });
 
-        let output = if self.pass.pass_type == "buffer" {
-            Some(self.pass.outputs[0].id)
-        } else {
-            None
+        if let Some(e) = self.device.pop_error_scope().await {
+            return Err(format!("Pass '{}': {}", self.pass.name, e).into());
+        }
+
+        let output = match self.pass.output {
+            PassOutput::Buffer(id) => {
+                self.buffer_texture(id);
+                Some(id)
+            }
+            PassOutput::Screen => None,
         };
 
-        RenderPass {
+        Ok(RenderPass {
             output,
             name: self.pass.name.to_string(),
             pipeline,
             bind_groups: self.bind_groups,
-        }
+        })
     }
 
-    pub async fn add_input(&mut self, input: &RenderPassInput) -> Result<(), Box<dyn Error>> {
-        if input.ctype == "texture" || input.ctype == "cubemap" {
-            self.handle_texture_input(input).await?;
-        }
-
-        if input.ctype == "keyboard" {
-            self.handle_keyboard_input(input);
-        }
-
-        if input.ctype == "buffer" {
-            self.handle_buffer_input(input);
+    pub async fn add_channel(&mut self, channel: &Channel) -> Result<(), Box<dyn Error>> {
+        match channel.source {
+            ChannelSource::Texture(ref src) => {
+                self.handle_texture_input(channel, src, InputType::D2).await?;
+            }
+            ChannelSource::Cubemap(ref src) => {
+                self.handle_texture_input(channel, src, InputType::Cube).await?;
+            }
+            ChannelSource::Keyboard => self.handle_color_input(channel, [255, 0, 0, 255]),
+            ChannelSource::Black => self.handle_color_input(channel, [0, 0, 0, 255]),
+            ChannelSource::Buffer(id) => self.handle_buffer_input(channel, id),
         }
 
         Ok(())
     }
 
-    fn handle_keyboard_input(&mut self, input: &RenderPassInput) {
+    fn handle_color_input(&mut self, channel: &Channel, color: [u8; 4]) {
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("destination"),
             size: wgpu::Extent3d::default(),
@@ -297,7 +315,7 @@ impl<'a> PipelineBuilder<'a> {
 
         self.queue.write_texture(
             texture.as_image_copy(),
-            &[255, 0, 0, 255],
+            &color,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: std::num::NonZeroU32::new(4 * 1),
@@ -306,13 +324,13 @@ impl<'a> PipelineBuilder<'a> {
             wgpu::Extent3d::default(),
         );
 
-        self.add_renderpass_from_texture(Ok(&texture), input, InputType::D2);
+        self.add_renderpass_from_texture(Ok(&texture), channel, InputType::D2);
     }
 
     fn add_renderpass_from_texture(
         &mut self,
         texture: Result<&Texture, wgpu::TextureView>,
-        input: &RenderPassInput,
+        channel: &Channel,
         input_type: InputType,
     ) {
         let texture_view = match texture {
@@ -323,18 +341,18 @@ impl<'a> PipelineBuilder<'a> {
             }),
             Err(t) => t,
         };
-        let sampler = self.create_sampler();
+        let sampler = self.create_sampler(&channel.sampler);
         let sampler_layout = self.sampler_layout(input_type.into());
         let bind_group = self.bind_group(texture_view, sampler, &sampler_layout);
 
         self.bind_groups.push(bind_group);
         self.bind_group_layouts.push(sampler_layout);
 
-        self.add_sampler(input.channel, input_type);
+        self.add_sampler(channel.index, input_type);
     }
 
-    fn handle_buffer_input(&mut self, input: &RenderPassInput) {
-        if !self.textures.contains_key(&input.id) {
+    fn buffer_texture(&mut self, id: u64) -> &Texture {
+        if !self.textures.contains_key(&id) {
             let texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("destination"),
                 size: wgpu::Extent3d {
@@ -351,32 +369,39 @@ impl<'a> PipelineBuilder<'a> {
                 view_formats: &[],
             });
 
-            self.textures.insert(input.id, texture);
+            self.textures.insert(id, texture);
         }
 
+        &self.textures[&id]
+    }
+
+    fn handle_buffer_input(&mut self, channel: &Channel, id: u64) {
         let input_type = InputType::D2;
-        let view = self.textures[&input.id].create_view(&wgpu::TextureViewDescriptor {
-            label: None,
-            dimension: Some(input_type.into()),
-            ..wgpu::TextureViewDescriptor::default()
-        });
-        self.add_renderpass_from_texture(Err(view), input, input_type);
+        let view = self
+            .buffer_texture(id)
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: None,
+                dimension: Some(input_type.into()),
+                ..wgpu::TextureViewDescriptor::default()
+            });
+        self.add_renderpass_from_texture(Err(view), channel, input_type);
     }
 
     async fn handle_texture_input(
         &mut self,
-        input: &RenderPassInput,
+        channel: &Channel,
+        src: &str,
+        input_type: InputType,
     ) -> Result<(), Box<dyn Error>> {
-        let input_type = InputType::from_ctype(&input.ctype);
-        let (image, (width, height)) = self.client.get_png(&input.src, input_type).await?;
-        println!(
-            "Image info {:?} ({} {})",
+        let (image, (width, height)) = self.client.get_png(src, input_type).await?;
+        eprintln!(
+            "Image info {:?} ({} channel {})",
             width * height,
-            input.id,
-            input.ctype
+            src,
+            channel.index
         );
 
-        match input.channel {
+        match channel.index {
             0 => self.uniform.channel_0 = [width as f32, height as f32, 0.0, 0.0],
             1 => self.uniform.channel_1 = [width as f32, height as f32, 0.0, 0.0],
             2 => self.uniform.channel_2 = [width as f32, height as f32, 0.0, 0.0],
@@ -390,14 +415,14 @@ impl<'a> PipelineBuilder<'a> {
             depth_or_array_layers: if input_type.is_cube() { 6 } else { 1 },
         };
 
-        let format = if input.sampler.srgb == "true" {
+        let format = if channel.sampler.srgb {
             wgpu::TextureFormat::Bgra8UnormSrgb
         } else {
             wgpu::TextureFormat::Bgra8Unorm
         };
 
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&input.src),
+            label: Some(src),
             size: texture_size,
             mip_level_count: 1,
             sample_count: 1,
@@ -423,7 +448,7 @@ impl<'a> PipelineBuilder<'a> {
             texture_size, // Fuck you
         );
 
-        self.add_renderpass_from_texture(Ok(&texture), input, input_type);
+        self.add_renderpass_from_texture(Ok(&texture), channel, input_type);
         Ok(())
     }
 
@@ -475,25 +500,59 @@ impl<'a> PipelineBuilder<'a> {
             })
     }
 
-    fn create_sampler(&mut self) -> wgpu::Sampler {
+    fn create_sampler(&mut self, desc: &SamplerDesc) -> wgpu::Sampler {
+        let address_mode = match desc.wrap {
+            Wrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            Wrap::Repeat => wgpu::AddressMode::Repeat,
+        };
+        let filter = match desc.filter {
+            Filter::Nearest => wgpu::FilterMode::Nearest,
+            Filter::Linear => wgpu::FilterMode::Linear,
+        };
+
         self.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: filter,
+            min_filter: filter,
             mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         })
     }
 }
 
+/// Offset added to iTime, so shaders shown side by side don't run in lockstep.
+/// Use `Fixed(0.0)` to disable it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimeOffset {
+    /// A random offset between 0 and 100 seconds
+    #[default]
+    Random,
+    /// A random offset between 0 and 100 seconds, the same for every run with this seed
+    Seeded(u64),
+    Fixed(f32),
+}
+
+impl TimeOffset {
+    pub fn seconds(&self) -> f32 {
+        match self {
+            TimeOffset::Random => WyRand::new().generate_range(0..10000) as f32 / 100.0,
+            TimeOffset::Seeded(seed) => {
+                WyRand::new_seed(*seed).generate_range(0..10000) as f32 / 100.0
+            }
+            TimeOffset::Fixed(x) => *x,
+        }
+    }
+}
+
 pub struct Args {
     pub rps: Vec<super::RenderPass>,
     pub client: Client,
     pub name: String,
     pub width: f32,
     pub height: f32,
+    pub offset: TimeOffset,
 }
 impl Args {
     pub async fn from_source(
@@ -512,7 +571,7 @@ impl Args {
             code: source,
             name: "Source Shader".into(),
             description: "".into(),
-            pass_type: "image".into(),
+            pass_type: PassType::Image,
         }];
 
         Ok(Args {
@@ -521,46 +580,46 @@ impl Args {
             name: loc.unwrap_or("cyber_fuji".to_string()),
             width,
             height,
+            offset: TimeOffset::Random,
         })
     }
     pub async fn from_local(
-        api: &str,
+        client: &Client,
         loc: String,
         width: f32,
         height: f32,
     ) -> Result<Self, Box<dyn Error>> {
         let st = read_to_string(loc).await?;
-        let shader: super::Shader = serde_json::from_str(&st)?;
-
-        let client = Client::new(&api);
+        let shader = super::Shader::from_json(&st, ParseMode::Lenient)?;
 
         Ok(Args {
             rps: shader.renderpass,
-            client,
+            client: client.clone(),
             name: shader.info.name,
             width,
             height,
+            offset: TimeOffset::Random,
         })
     }
 
     pub async fn from_toy(
-        api: &str,
+        client: &Client,
         shader_id: String,
         save: Option<String>,
         width: f32,
         height: f32,
     ) -> Result<Self, Box<dyn Error>> {
-        let client = Client::new(&api);
         let shader = client
             .get_shader(&shader_id, save.as_ref().map(|x| x.as_str()))
             .await?;
 
         Ok(Args {
             rps: shader.renderpass,
-            client,
+            client: client.clone(),
             name: shader.info.name,
             width,
             height,
+            offset: TimeOffset::Random,
         })
     }
 }
@@ -576,7 +635,10 @@ pub struct Example {
     rps: Vec<RenderPass>,
     textures: HashMap<u64, Texture>,
 
-    delta: f32,
+    /// Added to the time passed to `update`
+    offset: f32,
+    /// Time passed to the previous `update`
+    last_time: Option<f32>,
 }
 
 #[async_trait::async_trait]
@@ -655,28 +717,18 @@ impl RenderableConfig for Example {
 
         let mut rps = Vec::new();
 
-        let common_code: String = args
-            .rps
-            .iter()
-            .filter(|x| x.pass_type == "common")
-            .map(|x| &x.code)
-            .fold(String::new(), |acc, st| acc + st);
-
-        for (i, pass) in args
-            .rps
-            .into_iter()
-            .filter(|x| x.pass_type != "common" && x.pass_type != "sound")
-            .rev()
-            .enumerate()
-        {
+        let program = Program::new(&args.name, &args.rps)?;
+        program.warn_skipped();
+
+        for (i, pass) in program.passes.iter().enumerate() {
             let mut builder =
-                PipelineBuilder::new(&common, &mut uniform, &mut textures, &pass, &args.name, i);
+                PipelineBuilder::new(&common, &mut uniform, &mut textures, pass, &args.name, i);
 
-            for input in &pass.inputs {
-                builder.add_input(input).await?;
+            for channel in &pass.channels {
+                builder.add_channel(channel).await?;
             }
 
-            rps.push(builder.build(layouts, &common_code));
+            rps.push(builder.build(layouts, &program.common).await?);
         }
 
         let uniform_ref: &[Uniform; 1] = &[uniform];
@@ -696,7 +748,6 @@ impl RenderableConfig for Example {
             label: None,
         });
 
-        let mut rng = WyRand::new();
         // Done
         Ok(Example {
             vertex_buf,
@@ -707,7 +758,8 @@ impl RenderableConfig for Example {
             uniform_buf,
             rps,
             textures,
-            delta: rng.generate_range(0..10000) as f32 / 100.0,
+            offset: args.offset.seconds(),
+            last_time: None,
         })
     }
 }
@@ -720,9 +772,11 @@ impl Renderable for Example {
         _device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
-        let delta = accum_time - self.uniform.time;
-        self.uniform.time_delta = delta;
-        self.uniform.time = accum_time + self.delta;
+        // Seeking back doesn't make time run backwards for the shader
+        let delta = self.last_time.map(|last| accum_time - last).unwrap_or(0.0);
+        self.last_time = Some(accum_time);
+        self.uniform.time_delta = delta.max(0.0);
+        self.uniform.time = accum_time + self.offset;
         self.uniform.frame += 1;
         self.uniform.resolution = [size.0 as f32, size.1 as f32, 0., 0.];
 
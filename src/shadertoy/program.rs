@@ -0,0 +1,226 @@
+// Validated intermediate representation of a shader toy shader.
+// The pipeline builder only consumes this, so all checks on the json types happen here.
+
+use std::fmt;
+
+use error_chain::bail;
+
+use super::errors::*;
+use super::types::{ChannelType, FilterMode, PassType, RenderPass, RenderPassInput, WrapMode};
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub name: String,
+    /// Code of all `common` passes, shared by every pass
+    pub common: String,
+    /// Passes in render order, the pass rendering to the screen is last
+    pub passes: Vec<Pass>,
+    /// Everything that was dropped while building the program
+    pub skipped: Vec<Skipped>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pass {
+    pub name: String,
+    pub code: String,
+    pub output: PassOutput,
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassOutput {
+    Screen,
+    /// Renders to the buffer texture with this id
+    Buffer(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    /// The `N` in `iChannelN`
+    pub index: u64,
+    pub source: ChannelSource,
+    pub sampler: SamplerDesc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelSource {
+    Texture(String),
+    Cubemap(String),
+    /// Output of the buffer pass with this id
+    Buffer(u64),
+    Keyboard,
+    /// Stand-in for an unsupported input, so the shader still compiles
+    Black,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerDesc {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub srgb: bool,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc {
+            filter: Filter::Linear,
+            wrap: Wrap::Repeat,
+            srgb: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub pass: String,
+    pub what: String,
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}': {}", self.pass, self.what)
+    }
+}
+
+fn sampler_desc(input: &RenderPassInput) -> SamplerDesc {
+    let filter = match input.sampler.filter {
+        FilterMode::Nearest => Filter::Nearest,
+        _ => Filter::Linear,
+    };
+    let wrap = match input.sampler.wrap {
+        WrapMode::Clamp => Wrap::Clamp,
+        _ => Wrap::Repeat,
+    };
+
+    SamplerDesc {
+        filter,
+        wrap,
+        srgb: input.sampler.srgb,
+    }
+}
+
+impl Program {
+    pub fn new(name: &str, passes: &[RenderPass]) -> Result<Program> {
+        let mut skipped = Vec::new();
+
+        let common = passes
+            .iter()
+            .filter(|x| x.pass_type == PassType::Common)
+            .map(|x| x.code.as_str())
+            .collect::<String>();
+
+        let buffers: Vec<u64> = passes
+            .iter()
+            .filter(|x| x.pass_type == PassType::Buffer)
+            .filter_map(|x| x.outputs.first().map(|o| o.id))
+            .collect();
+
+        let mut out = Vec::new();
+        for pass in passes {
+            let output = match pass.pass_type {
+                PassType::Image => PassOutput::Screen,
+                PassType::Buffer => match pass.outputs.first() {
+                    Some(o) => PassOutput::Buffer(o.id),
+                    None => bail!("Buffer pass '{}' has no output", pass.name),
+                },
+                PassType::Common => continue,
+                ref other => {
+                    skipped.push(Skipped {
+                        pass: pass.name.clone(),
+                        what: format!("{} pass", other),
+                    });
+                    continue;
+                }
+            };
+
+            let mut channels: Vec<Channel> = Vec::new();
+            for input in &pass.inputs {
+                let mut skip = |what: String| {
+                    skipped.push(Skipped {
+                        pass: pass.name.clone(),
+                        what: format!("channel {} ({})", input.channel, what),
+                    })
+                };
+
+                if input.channel > 3 {
+                    skip(format!("{}, channel out of range", input.ctype));
+                    continue;
+                }
+                if channels.iter().any(|c| c.index == input.channel) {
+                    skip(format!("{}, channel used twice", input.ctype));
+                    continue;
+                }
+
+                let source = match input.ctype {
+                    ChannelType::Texture => ChannelSource::Texture(input.src.clone()),
+                    ChannelType::Cubemap => ChannelSource::Cubemap(input.src.clone()),
+                    ChannelType::Keyboard => ChannelSource::Keyboard,
+                    ChannelType::Buffer if buffers.contains(&input.id) => {
+                        ChannelSource::Buffer(input.id)
+                    }
+                    ChannelType::Buffer => {
+                        skip(format!("buffer {} is not rendered by any pass", input.id));
+                        ChannelSource::Black
+                    }
+                    ref other => {
+                        skip(other.to_string());
+                        ChannelSource::Black
+                    }
+                };
+
+                channels.push(Channel {
+                    index: input.channel,
+                    source,
+                    sampler: sampler_desc(input),
+                });
+            }
+
+            out.push(Pass {
+                name: pass.name.clone(),
+                code: pass.code.clone(),
+                output,
+                channels,
+            });
+        }
+
+        if !out.iter().any(|p| p.output == PassOutput::Screen) {
+            bail!("Shader '{}' has no image pass", name);
+        }
+        // Buffers render in the order they are listed, like on shader toy, the image pass reads them last
+        out.sort_by_key(|p| p.output == PassOutput::Screen);
+
+        Ok(Program {
+            name: name.to_string(),
+            common,
+            passes: out,
+            skipped,
+        })
+    }
+
+    /// Prints a warning listing everything that was skipped.
+    pub fn warn_skipped(&self) {
+        if self.skipped.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "Warning: '{}' uses unsupported inputs, these render black or not at all:",
+            self.name
+        );
+        for skipped in &self.skipped {
+            eprintln!("  - {}", skipped);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use super::client::*;
+use super::errors::*;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A human readable search, as used in the playlist config.
+///
+/// The grammar is `[top <n>] [<sort>] [matching <words>] [with filter <filter>[, <filter>]]`,
+/// for example `top 20 popular with filter multipass` or `top 5 newest matching fire`.
+/// All keywords are case insensitive.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SearchQuery {
+    pub string: String,
+    pub sort_order: SearchSortOrder,
+    pub filters: Vec<SearchFilter>,
+    /// Maximum amount of shader ids to keep, `None` keeps everything the API returns.
+    pub limit: Option<usize>,
+}
+
+fn parse_sort_order(word: &str) -> Option<SearchSortOrder> {
+    match word {
+        "name" => Some(SearchSortOrder::Name),
+        "love" | "loved" => Some(SearchSortOrder::Love),
+        "popular" => Some(SearchSortOrder::Popular),
+        "newest" | "new" => Some(SearchSortOrder::Newest),
+        "hot" => Some(SearchSortOrder::Hot),
+        _ => None,
+    }
+}
+
+fn parse_filter(word: &str) -> Option<SearchFilter> {
+    match word {
+        "vr" => Some(SearchFilter::Vr),
+        "soundoutput" => Some(SearchFilter::SoundOutput),
+        "soundinput" => Some(SearchFilter::SoundInput),
+        "webcam" => Some(SearchFilter::Webcam),
+        "multipass" => Some(SearchFilter::MultiPass),
+        "musicstream" => Some(SearchFilter::MusicStream),
+        _ => None,
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SearchQuery> {
+        let words: Vec<String> = s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        let mut query = SearchQuery {
+            string: String::new(),
+            sort_order: SearchSortOrder::Popular,
+            filters: Vec::new(),
+            limit: None,
+        };
+
+        let mut i = 0;
+        while i < words.len() {
+            match words[i].as_str() {
+                "top" => {
+                    let n = words.get(i + 1).and_then(|n| n.parse().ok());
+                    match n {
+                        Some(n) => query.limit = Some(n),
+                        None => bail!("Expected a number after 'top' in search '{}'", s),
+                    }
+                    i += 2;
+                }
+                "matching" => {
+                    let end = words[i + 1..]
+                        .iter()
+                        .position(|w| w == "with")
+                        .map(|p| i + 1 + p)
+                        .unwrap_or(words.len());
+                    if end == i + 1 {
+                        bail!("Expected words after 'matching' in search '{}'", s);
+                    }
+                    query.string = words[i + 1..end].join(" ");
+                    i = end;
+                }
+                "with" => {
+                    if words.get(i + 1).map(|w| w.as_str()) == Some("filter") {
+                        i += 1;
+                    }
+                    i += 1;
+                    let start = i;
+                    while let Some(filter) = words.get(i).and_then(|w| parse_filter(w)) {
+                        query.filters.push(filter);
+                        i += 1;
+                    }
+                    if i == start {
+                        bail!("Expected a filter after 'with' in search '{}'", s);
+                    }
+                }
+                word => match parse_sort_order(word) {
+                    Some(order) => {
+                        query.sort_order = order;
+                        i += 1;
+                    }
+                    None => bail!("Unexpected '{}' in search '{}'", word, s),
+                },
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+impl SearchQuery {
+    pub fn params(&self) -> SearchParams<'_> {
+        SearchParams {
+            string: &self.string,
+            sort_order: self.sort_order,
+            filters: self.filters.clone(),
+        }
+    }
+
+    /// File name used to cache the results of this query.
+    fn cache_name(&self) -> String {
+        let filters: Vec<_> = self
+            .filters
+            .iter()
+            .map(|f| format!("{:?}", f).to_lowercase())
+            .collect();
+        let name = format!(
+            "{:?}_{}_{}_{}",
+            self.sort_order,
+            self.limit.map(|l| l.to_string()).unwrap_or_default(),
+            self.string,
+            filters.join("-")
+        );
+
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect::<String>()
+            .to_lowercase()
+            + ".json"
+    }
+
+    /// Resolves this query into shader ids.
+    ///
+    /// When `cached` is set, results are read from and written to the `search` directory
+    /// of the client's cache, so the API is only queried the first time.
+    pub async fn resolve(&self, client: &Client, cached: bool) -> Result<Vec<String>> {
+        let resource = format!("search/{}", self.cache_name());
+
+        if cached {
+            if let Some(bytes) = client.cache.get(&resource).await {
+                if let Ok(ids) = serde_json::from_slice::<Vec<String>>(&bytes) {
+                    return Ok(ids);
+                }
+            }
+        }
+
+        let mut ids = client.search(&self.params()).await?;
+        if let Some(limit) = self.limit {
+            ids.truncate(limit);
+        }
+
+        if cached {
+            let url = client.search_url(&self.params());
+            client
+                .cache
+                .put(&resource, &url, &serde_json::to_vec_pretty(&ids)?)
+                .await?;
+        }
+
+        Ok(ids)
+    }
+}
@@ -1,18 +1,29 @@
 // These are the json types that the Shadertoy API outputs
 // This is manually derived
+//
+// Parsing is lenient: unknown fields are ignored and most fields have defaults,
+// so JSON from other tools, older API versions and hand-written manifests loads.
+// Use `Shader::from_json` with `ParseMode::Strict` to validate against the API format.
 
-use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use error_chain::bail;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use super::errors::*;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
 pub struct Shader {
+    #[serde(default)]
     pub ver: String,
+    #[serde(default)]
     pub info: ShaderInfo,
     pub renderpass: Vec<RenderPass>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct ShaderInfo {
     pub id: String,
@@ -30,44 +41,284 @@ pub struct ShaderInfo {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
 pub struct RenderPass {
+    #[serde(default)]
     pub inputs: Vec<RenderPassInput>,
+    #[serde(default)]
     pub outputs: Vec<RenderPassOutput>,
     pub code: String,
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub description: String,
 
     #[serde(rename = "type")]
-    pub pass_type: String,
+    #[serde(default)]
+    pub pass_type: PassType,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct RenderPassInput {
     pub id: u64,
     pub src: String,
-    pub ctype: String,
+    pub ctype: ChannelType,
     pub channel: u64,
     pub sampler: Sampler,
     pub published: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct RenderPassOutput {
     pub id: u64,
     pub channel: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(deny_unknown_fields)]
+#[serde(default)]
 pub struct Sampler {
-    pub filter: String,
-    pub wrap: String,
-    pub vflip: String,
-    pub srgb: String,
-    //pub vflip: bool,
-    //pub srgb: bool,
+    pub filter: FilterMode,
+    pub wrap: WrapMode,
+    #[serde(with = "string_bool")]
+    pub vflip: bool,
+    #[serde(with = "string_bool")]
+    pub srgb: bool,
     pub internal: String,
 }
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            filter: FilterMode::Mipmap,
+            wrap: WrapMode::Repeat,
+            vflip: true,
+            srgb: false,
+            internal: "byte".into(),
+        }
+    }
+}
+
+/// Shadertoy writes booleans as `"true"` and `"false"`, other tools use real booleans.
+/// Both load, strict mode only accepts the strings.
+mod string_bool {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &bool,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *value { "true" } else { "false" })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<bool, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Bool(b) => Ok(b),
+            Value::String(s) if s == "true" => Ok(true),
+            Value::String(s) if s == "false" => Ok(false),
+            other => Err(serde::de::Error::custom(format!(
+                "expected a boolean, found {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Declares a string backed enum, with an `Unknown` variant for values we don't know about.
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident, $default:ident, { $($variant:ident => $st:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $st,)*
+                    $name::Unknown(st) => st,
+                }
+            }
+
+            pub fn is_unknown(&self) -> bool {
+                matches!(self, $name::Unknown(_))
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::$default
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(st: &str) -> Self {
+                match st {
+                    $($st => $name::$variant,)*
+                    other => $name::Unknown(other.to_string()),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                let st = String::deserialize(deserializer)?;
+                Ok($name::from(st.as_str()))
+            }
+        }
+    };
+}
+
+string_enum!(
+    /// The `ctype` of a render pass input
+    ChannelType, Texture, {
+        Texture => "texture",
+        Cubemap => "cubemap",
+        Buffer => "buffer",
+        Keyboard => "keyboard",
+        Volume => "volume",
+        Video => "video",
+        Music => "music",
+        MusicStream => "musicstream",
+        Mic => "mic",
+        Webcam => "webcam",
+    }
+);
+
+string_enum!(
+    /// The `type` of a render pass
+    PassType, Image, {
+        Image => "image",
+        Buffer => "buffer",
+        Common => "common",
+        Sound => "sound",
+        Cubemap => "cubemap",
+    }
+);
+
+string_enum!(
+    FilterMode, Mipmap, {
+        Nearest => "nearest",
+        Linear => "linear",
+        Mipmap => "mipmap",
+    }
+);
+
+string_enum!(
+    WrapMode, Repeat, {
+        Clamp => "clamp",
+        Repeat => "repeat",
+    }
+);
+
+/// How strictly `Shader::from_json` checks its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Fill in defaults for missing fields and ignore unknown ones.
+    #[default]
+    Lenient,
+    /// Require the exact Shadertoy API format, useful for CI.
+    Strict,
+}
+
+impl Shader {
+    pub fn from_json(json: &str, mode: ParseMode) -> Result<Shader> {
+        let shader: Shader = serde_json::from_str(json)?;
+
+        if mode == ParseMode::Strict {
+            let original: Value = serde_json::from_str(json)?;
+            let parsed = serde_json::to_value(&shader)?;
+
+            let mut problems = Vec::new();
+            compare_fields("", &original, &parsed, &mut problems);
+            shader.unknown_values(&mut problems);
+
+            if !problems.is_empty() {
+                bail!(ErrorKind::Validation(problems.join(", ")));
+            }
+        }
+
+        Ok(shader)
+    }
+
+    fn unknown_values(&self, problems: &mut Vec<String>) {
+        for (i, pass) in self.renderpass.iter().enumerate() {
+            if pass.pass_type.is_unknown() {
+                problems.push(format!("renderpass[{}]: unknown type '{}'", i, pass.pass_type));
+            }
+
+            for (j, input) in pass.inputs.iter().enumerate() {
+                let at = format!("renderpass[{}].inputs[{}]", i, j);
+                if input.ctype.is_unknown() {
+                    problems.push(format!("{}: unknown ctype '{}'", at, input.ctype));
+                }
+                if input.sampler.filter.is_unknown() {
+                    problems.push(format!("{}: unknown filter '{}'", at, input.sampler.filter));
+                }
+                if input.sampler.wrap.is_unknown() {
+                    problems.push(format!("{}: unknown wrap '{}'", at, input.sampler.wrap));
+                }
+            }
+        }
+    }
+}
+
+/// Name of the JSON type of `value`, for messages.
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Every field that was filled in by a default is missing from the original,
+/// every field that was dropped while parsing is unknown and
+/// every value that was coerced into another type has the wrong type.
+fn compare_fields(path: &str, original: &Value, parsed: &Value, problems: &mut Vec<String>) {
+    match (original, parsed) {
+        (Value::Object(original), Value::Object(parsed)) => {
+            for key in original.keys().filter(|k| !parsed.contains_key(*k)) {
+                problems.push(format!("{}.{}: unknown field", path, key));
+            }
+            for (key, value) in parsed {
+                let at = format!("{}.{}", path, key);
+                match original.get(key) {
+                    Some(o) => compare_fields(&at, o, value, problems),
+                    None => problems.push(format!("{}: missing field", at)),
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(parsed)) => {
+            for (i, (o, p)) in original.iter().zip(parsed).enumerate() {
+                compare_fields(&format!("{}[{}]", path, i), o, p, problems);
+            }
+        }
+        (original, parsed) if json_type(original) != json_type(parsed) => problems.push(format!(
+            "{}: expected {}, found {}",
+            path,
+            json_type(parsed),
+            json_type(original)
+        )),
+        _ => {}
+    }
+}
@@ -107,13 +107,6 @@ impl InputType {
         }
     }
 
-    pub fn from_ctype(ctype: &str) -> Self {
-       if ctype == "cubemap" {
-           Self::Cube
-       } else {
-           Self::D2
-       }
-    }
     pub fn is_cube(&self) -> bool {
         match self {
             InputType::Cube => true,
@@ -0,0 +1,166 @@
+// Uncompressed video streams for external encoders, like `imager ... | ffmpeg -i - out.mp4`.
+//
+// Y4M is understood by most encoders and players. The raw format is simpler to read yourself:
+// a header of `IMGRAW1\0`, width and height as u32 and fps as f32, followed by frames
+// that each start with their index as u64 and then `width * height` BGRA pixels.
+// All numbers are little endian.
+
+use std::{error::Error, io::Write};
+
+use crate::screenshot::Frame;
+
+pub const RAW_MAGIC: &[u8; 8] = b"IMGRAW1\0";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Chroma {
+    /// Chroma at half resolution in both directions, centered like JPEG
+    C420jpeg,
+    C444,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    Y4m(Chroma),
+    RawBgra,
+}
+
+/// Writes frames of a fixed size as a video stream, the header is written with the first frame.
+pub struct VideoWriter<W> {
+    out: W,
+    format: VideoFormat,
+    width: u32,
+    height: u32,
+    fps: f32,
+    frames: u64,
+}
+
+/// BT.601 full range, as used by JPEG.
+fn bgra_to_ycbcr(px: &[u8]) -> (f32, f32, f32) {
+    let (b, g, r) = (px[0] as f32, px[1] as f32, px[2] as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+    (y, cb, cr)
+}
+
+fn to_u8(v: f32) -> u8 {
+    v.round().clamp(0.0, 255.0) as u8
+}
+
+/// Frame rate as a fraction, Y4M doesn't allow fractional rates.
+fn rate(fps: f32) -> (u32, u32) {
+    if fps.fract() == 0.0 {
+        (fps as u32, 1)
+    } else {
+        ((fps * 1000.0).round() as u32, 1000)
+    }
+}
+
+impl<W: Write> VideoWriter<W> {
+    pub fn new(out: W, format: VideoFormat, width: u32, height: u32, fps: f32) -> Self {
+        Self {
+            out,
+            format,
+            width,
+            height,
+            fps,
+            frames: 0,
+        }
+    }
+
+    fn header(&mut self) -> std::io::Result<()> {
+        match self.format {
+            VideoFormat::Y4m(chroma) => {
+                let (num, den) = rate(self.fps);
+                let chroma = match chroma {
+                    Chroma::C420jpeg => "C420jpeg",
+                    Chroma::C444 => "C444",
+                };
+                writeln!(
+                    self.out,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 {} XCOLORRANGE=FULL",
+                    self.width, self.height, num, den, chroma
+                )
+            }
+            VideoFormat::RawBgra => {
+                self.out.write_all(RAW_MAGIC)?;
+                self.out.write_all(&self.width.to_le_bytes())?;
+                self.out.write_all(&self.height.to_le_bytes())?;
+                self.out.write_all(&self.fps.to_le_bytes())
+            }
+        }
+    }
+
+    pub fn write(&mut self, frame: &Frame) -> Result<(), Box<dyn Error>> {
+        if (frame.width, frame.height) != (self.width, self.height) {
+            return Err(format!(
+                "Frame is {}x{}, but the stream is {}x{}",
+                frame.width, frame.height, self.width, self.height
+            )
+            .into());
+        }
+
+        if self.frames == 0 {
+            self.header()?;
+        }
+
+        match self.format {
+            VideoFormat::Y4m(chroma) => {
+                self.out.write_all(b"FRAME\n")?;
+                let planes = self.planes(frame, chroma);
+                for plane in &planes {
+                    self.out.write_all(plane)?;
+                }
+            }
+            VideoFormat::RawBgra => {
+                self.out.write_all(&self.frames.to_le_bytes())?;
+                self.out.write_all(&frame.buffer)?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Y, Cb and Cr planes of a frame.
+    fn planes(&self, frame: &Frame, chroma: Chroma) -> [Vec<u8>; 3] {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let pixels: Vec<_> = frame.buffer.chunks_exact(4).map(bgra_to_ycbcr).collect();
+
+        let luma = pixels.iter().map(|p| to_u8(p.0)).collect();
+        match chroma {
+            Chroma::C444 => [
+                luma,
+                pixels.iter().map(|p| to_u8(p.1)).collect(),
+                pixels.iter().map(|p| to_u8(p.2)).collect(),
+            ],
+            Chroma::C420jpeg => {
+                // Average 2x2 blocks, odd sizes repeat the last row or column
+                let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+                let mut cb = Vec::with_capacity(cw * ch);
+                let mut cr = Vec::with_capacity(cw * ch);
+
+                for y in 0..ch {
+                    for x in 0..cw {
+                        let (mut sb, mut sr) = (0.0, 0.0);
+                        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                            let px = (2 * x + dx).min(w - 1);
+                            let py = (2 * y + dy).min(h - 1);
+                            let p = pixels[py * w + px];
+                            sb += p.1;
+                            sr += p.2;
+                        }
+                        cb.push(to_u8(sb / 4.0));
+                        cr.push(to_u8(sr / 4.0));
+                    }
+                }
+
+                [luma, cb, cr]
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
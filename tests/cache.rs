@@ -0,0 +1,63 @@
+// The resource cache and its atomic writes, in a scratch directory per test.
+
+use std::path::PathBuf;
+
+use futures_util::future::try_join_all;
+use imager::shadertoy::{write_atomic, Cache};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[tokio::test]
+async fn concurrent_writes_of_a_path_all_succeed() {
+    let dir = scratch("concurrent_writes");
+    let path = dir.join("shader.json");
+
+    let contents: Vec<Vec<u8>> = (0..16u8).map(|i| vec![i; 4096]).collect();
+    try_join_all(contents.iter().map(|bytes| write_atomic(&path, bytes)))
+        .await
+        .unwrap();
+
+    // One of the writes wins as a whole, and no temporary files are left behind
+    assert!(contents.contains(&std::fs::read(&path).unwrap()));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn untracked_files_are_not_served() {
+    let dir = scratch("untracked_files");
+    let cache = Cache::open(&dir);
+
+    write_atomic(&dir.join("media/a/stray.png"), b"stray")
+        .await
+        .unwrap();
+    assert_eq!(cache.get("/media/a/stray.png").await, None);
+
+    cache
+        .put("/media/a/kept.png", "url", b"kept")
+        .await
+        .unwrap();
+    assert_eq!(cache.get("/media/a/kept.png").await.unwrap(), b"kept");
+}
+
+#[tokio::test]
+async fn prune_removes_the_oldest_entries_until_the_cache_fits() {
+    let dir = scratch("prune");
+    let cache = Cache::open(&dir);
+    for name in ["a", "b", "c"] {
+        cache.put(name, "url", &[0; 10]).await.unwrap();
+    }
+
+    let removed = cache.prune(None, Some(15)).await.unwrap();
+    assert_eq!(removed, ["a", "b"]);
+    assert!(!dir.join("a").exists());
+
+    // The manifest on disk agrees
+    let reopened = Cache::open(&dir);
+    let keys: Vec<_> = reopened.entries().into_iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, ["c"]);
+    assert!(reopened.verify().await.is_empty());
+}
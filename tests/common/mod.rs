@@ -0,0 +1,28 @@
+// Fixtures shared by the tests drawing frames into pixel sinks.
+
+use imager::francis::FroxyConfig;
+
+pub fn alpha(x: u16, y: u16) -> u8 {
+    100 + (x + y) as u8 * 5
+}
+
+/// A BGRA frame where every pixel has its own colour.
+pub fn frame(width: u16, height: u16) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            buf.extend_from_slice(&[x as u8 * 10, y as u8 * 20, 200, alpha(x, y)]);
+        }
+    }
+    buf
+}
+
+pub fn region(x: u16, y: u16, width: u16, height: u16) -> FroxyConfig {
+    FroxyConfig {
+        x,
+        y,
+        width,
+        height,
+        port: 0,
+    }
+}
@@ -0,0 +1,35 @@
+// Writing contact sheets, with frames made up instead of rendered.
+
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+use imager::{
+    contact::{ContactSheet, Row},
+    francis::Entry,
+};
+
+#[test]
+fn times_that_round_alike_keep_their_own_thumbnails() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("contact");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let frames: Vec<_> = [10, 200]
+        .into_iter()
+        .map(|v| RgbaImage::from_pixel(4, 3, Rgba([v, v, v, 255])))
+        .collect();
+    let mut sheet = ContactSheet::new(4, 3, vec![1.001, 1.004]);
+    sheet.push(Row {
+        entry: Entry::Source("shaders/planet.glsl".into()),
+        name: "planet".into(),
+        frames: Ok(frames.clone()),
+    });
+    sheet.save(&dir).unwrap();
+
+    let html = std::fs::read_to_string(dir.join("index.html")).unwrap();
+    for (col, frame) in frames.iter().enumerate() {
+        let name = format!("thumbs/000_{:02}.png", col);
+        let saved = image::open(dir.join(&name)).unwrap().to_rgba8();
+        assert_eq!(&saved, frame);
+        assert!(html.contains(&format!("src=\"{}\"", name)));
+    }
+}
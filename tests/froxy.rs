@@ -0,0 +1,120 @@
+// The froxy wire format, and the stand-in server against the client.
+
+use imager::froxy::{
+    froxy_configs, load_layout, parse, serialize, ErrorKind, FroxyConfig, FroxyServer,
+};
+
+fn sections() -> Vec<FroxyConfig> {
+    vec![
+        FroxyConfig {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32,
+            port: 0,
+        },
+        FroxyConfig {
+            x: 64,
+            y: 0,
+            width: 50,
+            height: 20,
+            port: 9001,
+        },
+    ]
+}
+
+#[test]
+fn replies_round_trip() {
+    let reply = serialize(&sections());
+    assert_eq!(reply.len(), 8 + 2 * 16);
+    assert_eq!(parse(&reply).unwrap(), sections());
+
+    assert!(parse(&serialize(&[])).unwrap().is_empty());
+}
+
+#[test]
+fn sections_are_big_endian_width_height_x_y_port() {
+    let reply = serialize(&sections()[1..]);
+    assert_eq!(
+        reply,
+        [
+            0, 0, 0, 0, 0, 0, 0, 1, // count
+            0, 50, 0, 20, 0, 64, 0, 0, // width, height, x, y
+            0, 0, 0, 0, 0, 0, 0x23, 0x29, // port
+        ]
+    );
+}
+
+#[test]
+fn wrong_lengths_are_errors() {
+    let reply = serialize(&sections());
+
+    match parse(&reply[..5]).unwrap_err().kind() {
+        ErrorKind::Truncated(8, 5) => {}
+        e => panic!("unexpected error {}", e),
+    }
+    match parse(&reply[..reply.len() - 1]).unwrap_err().kind() {
+        ErrorKind::Truncated(40, 39) => {}
+        e => panic!("unexpected error {}", e),
+    }
+
+    let mut long = reply.clone();
+    long.extend_from_slice(&[1, 2, 3]);
+    match parse(&long).unwrap_err().kind() {
+        ErrorKind::Trailing(3) => {}
+        e => panic!("unexpected error {}", e),
+    }
+
+    // A huge count is rejected without trying to read that many sections
+    let mut huge = reply;
+    huge[..8].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(matches!(
+        parse(&huge).unwrap_err().kind(),
+        ErrorKind::Truncated(u64::MAX, 40)
+    ));
+}
+
+#[test]
+fn empty_sections_are_errors() {
+    let mut empty = sections();
+    empty[1].height = 0;
+    assert!(matches!(
+        parse(&serialize(&empty)).unwrap_err().kind(),
+        ErrorKind::EmptySection(1)
+    ));
+}
+
+#[tokio::test]
+async fn layouts_load_from_json() {
+    let dir = std::env::temp_dir().join(format!("froxy-layout-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("layout.json");
+    std::fs::write(
+        &path,
+        r#"[{"x": 0, "y": 0, "width": 64, "height": 32},
+            {"x": 64, "y": 0, "width": 50, "height": 20, "port": 9001}]"#,
+    )
+    .unwrap();
+    assert_eq!(load_layout(&path).await.unwrap(), sections());
+
+    std::fs::write(&path, r#"[{"x": 0, "y": 0, "width": 0, "height": 32}]"#).unwrap();
+    assert!(matches!(
+        load_layout(&path).await.unwrap_err().kind(),
+        ErrorKind::EmptySection(0)
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn the_stand_in_serves_its_layout() {
+    let server = FroxyServer::bind("127.0.0.1:0", &sections()).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    async_std::task::spawn(server.run());
+
+    // Every connection gets the whole layout
+    for _ in 0..3 {
+        assert_eq!(froxy_configs(addr).await.unwrap(), sections());
+    }
+}
@@ -0,0 +1,163 @@
+// The glitch stage of the post process pass, rendered on a software adapter like the
+// golden image tests.
+
+use std::path::Path;
+
+use image::RgbaImage;
+use imager::{
+    francis::Update,
+    screenshot::{scrot_new, AnimScrot, Ctx, FrameSettings, Glitch},
+    shadertoy::{self as shader_toy, Example, TimeOffset},
+};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 90;
+
+async fn planet(ctx: &Ctx) -> AnimScrot<Example> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/planet.glsl");
+    let mut args = shader_toy::Args::from_source(
+        Some(path.to_string_lossy().into_owned()),
+        WIDTH as f32,
+        HEIGHT as f32,
+    )
+    .await
+    .unwrap();
+    args.offset = TimeOffset::Fixed(0.0);
+    scrot_new::<Example>(ctx, WIDTH, HEIGHT, args)
+        .await
+        .unwrap()
+}
+
+fn with_glitch(glitch: Glitch) -> FrameSettings {
+    FrameSettings {
+        glitch,
+        ..FrameSettings::default()
+    }
+}
+
+fn dropout(dropout: f32) -> FrameSettings {
+    with_glitch(Glitch {
+        dropout,
+        ..Glitch::default()
+    })
+}
+
+async fn frame(
+    anim: &mut AnimScrot<Example>,
+    ctx: &Ctx,
+    time: f32,
+    settings: FrameSettings,
+) -> RgbaImage {
+    anim.frame_with(ctx, time, None, &settings)
+        .await
+        .unwrap()
+        .to_image()
+}
+
+#[tokio::test]
+async fn glitch_stage() {
+    let ctx = match Ctx::request::<Example>(true).await {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("Skipping glitch tests, no software adapter: {}", e);
+            return;
+        }
+    };
+    let mut anim = planet(&ctx).await;
+
+    // Without glitches the frames are the golden references,
+    // up to the rounding of other software adapters
+    let first = frame(&mut anim, &ctx, 1.0, with_glitch(Glitch::default())).await;
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/planet_1.00s.png");
+    let reference = image::open(reference).unwrap().into_rgba8();
+    let rounding = first
+        .as_raw()
+        .iter()
+        .zip(reference.as_raw())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max();
+    assert!(
+        rounding <= Some(2),
+        "differs from the golden reference by {:?}",
+        rounding
+    );
+    let later = frame(&mut anim, &ctx, 3.0, FrameSettings::default()).await;
+    assert!(first != later);
+
+    // Dropped pixels keep the previous frame
+    frame(&mut anim, &ctx, 1.0, FrameSettings::default()).await;
+    let dropped = frame(&mut anim, &ctx, 3.0, dropout(1.0)).await;
+    assert!(
+        dropped == first,
+        "dropping every pixel should keep the previous frame"
+    );
+
+    frame(&mut anim, &ctx, 1.0, FrameSettings::default()).await;
+    let half = frame(&mut anim, &ctx, 3.0, dropout(0.5)).await;
+    let kept = half
+        .pixels()
+        .zip(first.pixels())
+        .filter(|(h, f)| h == f)
+        .count();
+    let new = half
+        .pixels()
+        .zip(later.pixels())
+        .filter(|(h, l)| h == l)
+        .count();
+    assert!(half
+        .pixels()
+        .zip(first.pixels().zip(later.pixels()))
+        .all(|(h, (f, l))| h == f || h == l));
+    let total = (WIDTH * HEIGHT) as usize;
+    assert!(
+        kept > total / 4 && new > total / 4,
+        "kept {}, new {}",
+        kept,
+        new
+    );
+
+    // Nothing is dropped without a previous frame, at a new size or for another target
+    let mut fresh = planet(&ctx).await;
+    let undropped = frame(&mut fresh, &ctx, 1.0, dropout(1.0)).await;
+    assert!(undropped == first, "the first frame should drop no pixels");
+
+    fresh.forget_previous();
+    let undropped = frame(&mut fresh, &ctx, 3.0, dropout(1.0)).await;
+    assert!(undropped == later, "a forgotten frame should not be kept");
+
+    let resized = fresh
+        .frame_with(&ctx, 3.0, Some((WIDTH / 2, HEIGHT / 2)), &dropout(1.0))
+        .await
+        .unwrap()
+        .to_image();
+    assert!(resized.pixels().any(|p| p.0 != [0, 0, 0, 255]));
+}
+
+#[test]
+fn failure_updates_are_dropout() {
+    let update = |json: &str| serde_json::from_str::<Update>(json).unwrap();
+
+    assert_eq!(
+        update(r#"{"failure": 0.5}"#)
+            .glitch(Glitch::default())
+            .dropout,
+        0.5
+    );
+    // The new name wins
+    let both = update(r#"{"failure": 0.5, "dropout": 0.25}"#);
+    assert_eq!(both.glitch(Glitch::default()).dropout, 0.25);
+
+    let glitch = Glitch {
+        dropout: 0.2,
+        noise: 0.1,
+        ..Glitch::default()
+    };
+    let tearing = update(r#"{"tearing": 0.3}"#).glitch(glitch);
+    assert_eq!(
+        tearing,
+        Glitch {
+            tearing: 0.3,
+            ..glitch
+        }
+    );
+}
@@ -0,0 +1,298 @@
+// Golden image tests for the shader corpus.
+//
+// Every json shader in `downloads/` and every glsl shader in `shaders/` is rendered on a
+// software adapter at a few fixed times and compared against `tests/golden/<name>_<time>s.png`.
+// Textures are generated stand-ins put into a scratch cache, so textured shaders render
+// the same offline and without redistributing the shader toy media.
+//
+// Set `IMAGER_BLESS=1` to write the current output as the new references.
+// Failing comparisons write the output and a diff image to `target/tmp/golden`.
+
+use std::{
+    io::Cursor,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+};
+
+use futures_util::FutureExt;
+use image::{ImageOutputFormat, Rgba, RgbaImage};
+use imager::{
+    screenshot::{scrot_new, Ctx},
+    shadertoy::{self as shader_toy, ChannelSource, Client, ClientOptions, Program, TimeOffset},
+};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 90;
+const TIMES: [f32; 2] = [1.0, 3.0];
+/// Size of the stand-in textures, and of every cubemap face
+const TEXTURE_SIZE: u32 = 64;
+
+/// Colour difference in YIQ space above which pixels count as different, from 0 to 1
+const THRESHOLD: f32 = 0.1;
+/// Share of the pixels that may differ, adapters round differently
+const MAX_DIFFERENT: f32 = 0.01;
+
+/// Shaders that don't build yet, with the reason. Drop an entry once it builds.
+const KNOWN_BROKEN: &[(&str, &str)] = &[
+    ("procedureal", "textureSample in non-uniform control flow"),
+    ("real_eye_2", "the naga validator panics on it"),
+    ("synth", "rejected by the naga GLSL frontend"),
+];
+
+struct Case {
+    name: String,
+    path: PathBuf,
+    json: bool,
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = Vec::new();
+    for (dir, ext) in [("downloads", "json"), ("shaders", "glsl")] {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some(ext) {
+                continue;
+            }
+            cases.push(Case {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                json: ext == "json",
+                path,
+            });
+        }
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+fn yiq(p: &Rgba<u8>) -> [f32; 3] {
+    let [r, g, b] = [p[0] as f32, p[1] as f32, p[2] as f32];
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.596 * r - 0.274 * g - 0.322 * b,
+        0.211 * r - 0.523 * g + 0.312 * b,
+    ]
+}
+
+/// Perceptual difference between two colours, 0 is equal and 1 is black against white.
+fn colour_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let (a, b) = (yiq(a), yiq(b));
+    let (y, i, q) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    (delta / 35215.0).sqrt()
+}
+
+/// Compares two images, returning the share of different pixels and an image marking them red.
+fn compare(expected: &RgbaImage, actual: &RgbaImage) -> (f32, RgbaImage) {
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut different = 0;
+
+    for (x, y, a) in actual.enumerate_pixels() {
+        let e = expected.get_pixel(x, y);
+        let marked = if colour_delta(e, a) > THRESHOLD {
+            different += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // Faded grayscale of the expected image, for context
+            let v = (yiq(e)[0] * 0.25 + 191.0) as u8;
+            Rgba([v, v, v, 255])
+        };
+        diff.put_pixel(x, y, marked);
+    }
+
+    let share = different as f32 / (actual.width() * actual.height()) as f32;
+    (share, diff)
+}
+
+fn reference_path(name: &str, time: f32) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}_{:.2}s.png", name, time))
+}
+
+/// A texture with a pattern of its own for every resource, encoded like the resource's extension.
+fn stand_in(resource: &str) -> Vec<u8> {
+    let seed = resource
+        .bytes()
+        .fold(2166136261u32, |h, b| (h ^ b as u32).wrapping_mul(16777619));
+    let [a, b, c, _] = seed.to_le_bytes();
+
+    let image = RgbaImage::from_fn(TEXTURE_SIZE, TEXTURE_SIZE, |x, y| {
+        let checker = if (x / 8 + y / 8) % 2 == 0 { 0 } else { 255 };
+        Rgba([(x * 4) as u8 ^ a, (y * 4) as u8 ^ b, checker ^ c, 255])
+    });
+
+    let format = if resource.ends_with(".jpg") {
+        ImageOutputFormat::Jpeg(90)
+    } else {
+        ImageOutputFormat::Png
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    image.write_to(&mut bytes, format).unwrap();
+    bytes.into_inner()
+}
+
+/// Caches stand-ins for every texture the program reads, cubemaps get all six faces.
+async fn cache_stand_ins(client: &Client, program: &Program) {
+    for channel in program.passes.iter().flat_map(|p| &p.channels) {
+        let (src, faces) = match &channel.source {
+            ChannelSource::Texture(src) => (src, 1),
+            ChannelSource::Cubemap(src) => (src, 6),
+            _ => continue,
+        };
+
+        let (stem, ext) = src.split_at(src.rfind('.').unwrap_or(src.len()));
+        for face in 0..faces {
+            let resource = match face {
+                0 => src.clone(),
+                face => format!("{}_{}{}", stem, face, ext),
+            };
+            if !client.cache.contains(&resource) {
+                let url = format!("stand-in for {}", resource);
+                client
+                    .cache
+                    .put(&resource, &url, &stand_in(&resource))
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+}
+
+async fn load(client: &Client, case: &Case) -> Result<shader_toy::Args, String> {
+    let (w, h) = (WIDTH as f32, HEIGHT as f32);
+    let path = case.path.to_string_lossy().into_owned();
+
+    let mut args = if case.json {
+        shader_toy::Args::from_local(client, path, w, h).await
+    } else {
+        shader_toy::Args::from_source(Some(path), w, h).await
+    }
+    .map_err(|e| format!("failed to load: {}", e))?;
+
+    args.offset = TimeOffset::Fixed(0.0);
+    Ok(args)
+}
+
+#[tokio::test]
+async fn golden_images() {
+    let ctx = match Ctx::request::<shader_toy::Example>(true).await {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("Skipping golden image tests, no software adapter: {}", e);
+            return;
+        }
+    };
+
+    let bless = std::env::var("IMAGER_BLESS").as_deref() == Ok("1");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let _ = std::fs::remove_dir_all(out_dir.join("cache"));
+
+    let client = Client::with_options(
+        "",
+        ClientOptions {
+            cache_root: out_dir.join("cache"),
+            offline: true,
+            ..ClientOptions::default()
+        },
+    )
+    .unwrap();
+
+    let mut failures = Vec::new();
+    for case in cases() {
+        let args = match load(&client, &case).await {
+            Ok(args) => args,
+            Err(e) => {
+                failures.push(format!("{}: {}", case.name, e));
+                continue;
+            }
+        };
+
+        let program = match Program::new(&args.name, &args.rps) {
+            Ok(program) => program,
+            Err(e) => {
+                failures.push(format!("{}: {}", case.name, e));
+                continue;
+            }
+        };
+        cache_stand_ins(&client, &program).await;
+
+        // Shader validation panics on some inputs, those count as failing to build
+        let built = AssertUnwindSafe(scrot_new::<shader_toy::Example>(&ctx, WIDTH, HEIGHT, args))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| Err("panicked while building".into()));
+        let mut anim = match built {
+            Ok(anim) => anim,
+            Err(e) => {
+                match KNOWN_BROKEN.iter().find(|(name, _)| *name == case.name) {
+                    Some((_, reason)) => {
+                        eprintln!("Skipping {}, known broken: {}", case.name, reason)
+                    }
+                    None => failures.push(format!("{}: failed to build: {}", case.name, e)),
+                }
+                continue;
+            }
+        };
+        if KNOWN_BROKEN.iter().any(|(name, _)| *name == case.name) {
+            eprintln!("{} builds now, remove it from KNOWN_BROKEN", case.name);
+        }
+
+        for time in TIMES {
+            let actual = anim.frame(&ctx, time, None).await.unwrap().to_image();
+            let reference = reference_path(&case.name, time);
+
+            if bless {
+                std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+                actual.save(&reference).unwrap();
+                continue;
+            }
+
+            let expected = match image::open(&reference) {
+                Ok(expected) => expected.into_rgba8(),
+                Err(_) => {
+                    failures.push(format!(
+                        "{}: no reference at {:?}, run with IMAGER_BLESS=1 to create it",
+                        case.name, reference
+                    ));
+                    continue;
+                }
+            };
+
+            if expected.dimensions() != actual.dimensions() {
+                failures.push(format!(
+                    "{} at {}s: size {:?}, expected {:?}",
+                    case.name,
+                    time,
+                    actual.dimensions(),
+                    expected.dimensions()
+                ));
+                continue;
+            }
+
+            let (different, diff) = compare(&expected, &actual);
+            if different > MAX_DIFFERENT {
+                let stem = format!("{}_{:.2}s", case.name, time);
+                actual.save(out_dir.join(format!("{}.png", stem))).unwrap();
+                diff.save(out_dir.join(format!("{}_diff.png", stem)))
+                    .unwrap();
+
+                failures.push(format!(
+                    "{} at {}s: {:.1}% of the pixels differ, see {:?}",
+                    case.name,
+                    time,
+                    different * 100.0,
+                    out_dir.join(format!("{}_diff.png", stem))
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} golden image failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
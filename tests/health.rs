@@ -0,0 +1,150 @@
+// Supervised targets reconnecting to local TCP stand-ins for francis.
+
+use std::{
+    io::Read,
+    net::TcpListener,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use imager::{
+    backoff::Backoff,
+    francis::{FroxyConfig, Health, Order, PixelSink, Protocol, SinkOptions, Supervised},
+};
+
+const REGION: FroxyConfig = FroxyConfig {
+    x: 0,
+    y: 0,
+    width: 4,
+    height: 3,
+    port: 0,
+};
+
+/// Sends every pixel of every frame, so every write reaches the server.
+const WHOLE_FRAMES: SinkOptions = SinkOptions {
+    offset: false,
+    alpha: false,
+    connections: 1,
+    batch: 5000,
+    diff: false,
+    threshold: 0,
+    refresh: None,
+    order: Order::Scanline,
+};
+
+fn frame() -> Vec<u8> {
+    vec![200; REGION.width as usize * REGION.height as usize * 4]
+}
+
+/// An address nothing listens on.
+fn closed_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// A server dropping its first connection right away, then going away.
+fn dropping_server() -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = thread::spawn(move || drop(listener.accept().unwrap()));
+    (addr, server)
+}
+
+/// A server reading a single connection on `addr`, returns how many bytes it sent.
+fn reading_server(addr: &str) -> JoinHandle<usize> {
+    let listener = TcpListener::bind(addr).unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        bytes.len()
+    })
+}
+
+#[test]
+fn backoff_doubles_up_to_the_max() {
+    let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1));
+    assert_eq!(backoff.delay(), Duration::from_millis(100));
+
+    let delays: Vec<_> = (0..5)
+        .map(|_| {
+            backoff.fail();
+            backoff.delay().as_millis()
+        })
+        .collect();
+    assert_eq!(delays, [100, 200, 400, 800, 1000]);
+
+    for _ in 0..100 {
+        backoff.fail();
+    }
+    assert_eq!(backoff.delay(), Duration::from_secs(1));
+
+    backoff.reset();
+    assert_eq!(backoff.failures(), 0);
+    assert_eq!(backoff.delay(), Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn target_down_at_startup_is_reported() {
+    let mut target = Supervised::connect(&closed_addr(), Protocol::Binary, REGION, WHOLE_FRAMES)
+        .await
+        .unwrap();
+
+    assert!(!target.is_healthy());
+    assert!(matches!(target.health(), Health::Down { failures: 1, .. }));
+
+    // Writes fail fast until the next reconnect is due
+    let error = target.write(frame(), 4).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotConnected);
+}
+
+#[tokio::test]
+async fn dropped_connection_recovers_after_the_delay() {
+    let (addr, server) = dropping_server();
+
+    let mut target = Supervised::connect(&addr, Protocol::Binary, REGION, WHOLE_FRAMES)
+        .await
+        .unwrap();
+    assert_eq!(target.health(), Health::Healthy);
+    server.join().unwrap();
+
+    // The first writes may still be buffered before the reset arrives
+    let mut failed = false;
+    for _ in 0..100 {
+        if target.write(frame(), 4).await.is_err() {
+            failed = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(failed, "writing to a dropped connection should fail");
+    assert!(matches!(target.health(), Health::Down { failures: 1, .. }));
+
+    let error = target.write(frame(), 4).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotConnected);
+
+    // The server is back, the next write after the delay reconnects
+    let server = reading_server(&addr);
+    let mut backoff = Backoff::default();
+    backoff.fail();
+    async_std::task::sleep(backoff.delay() + Duration::from_millis(50)).await;
+
+    assert_eq!(target.write(frame(), 4).await.unwrap(), 12);
+    assert_eq!(target.health(), Health::Healthy);
+    drop(target);
+
+    assert_eq!(server.join().unwrap(), 12 * 7);
+}
+
+#[tokio::test]
+async fn invalid_configurations_are_not_retried() {
+    let options = SinkOptions {
+        alpha: true,
+        ..SinkOptions::default()
+    };
+    let error = Supervised::connect(&closed_addr(), Protocol::Binary, REGION, options)
+        .await
+        .err()
+        .expect("the francis protocol has no alpha");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,130 @@
+// Pixel orders, as produced by the scatter of every sink.
+
+use std::collections::HashSet;
+
+use imager::francis::{Order, Scatter, SinkOptions};
+
+const ORDERS: [Order; 6] = [
+    Order::Random,
+    Order::Hilbert,
+    Order::ZOrder,
+    Order::Scanline,
+    Order::Importance,
+    Order::Dithered,
+];
+
+fn xy(width: u16, index: u32) -> (u32, u32) {
+    (index % width as u32, index / width as u32)
+}
+
+fn scatter(width: u16, height: u16, order: Order) -> Scatter {
+    let options = SinkOptions {
+        order,
+        ..SinkOptions::default()
+    };
+    Scatter::new(width, height, options)
+}
+
+#[test]
+fn every_order_sends_every_pixel_once() {
+    for order in ORDERS {
+        for (width, height) in [(1, 1), (7, 3), (16, 16), (33, 5)] {
+            let buf = vec![255; width as usize * height as usize * 4];
+            let pixels = scatter(width, height, order).pixels(buf, 4);
+
+            let unique: HashSet<_> = pixels.iter().map(|p| (p.x, p.y)).collect();
+            assert_eq!(pixels.len(), unique.len(), "{:?} repeats pixels", order);
+            assert_eq!(
+                unique.len(),
+                width as usize * height as usize,
+                "{:?} misses pixels",
+                order
+            );
+        }
+    }
+}
+
+#[test]
+fn orders_parse() {
+    for (name, order) in [
+        ("random", Order::Random),
+        ("Hilbert", Order::Hilbert),
+        ("z-order", Order::ZOrder),
+        ("scanline", Order::Scanline),
+        ("importance", Order::Importance),
+        ("bayer", Order::Dithered),
+    ] {
+        assert_eq!(name.parse::<Order>(), Ok(order));
+    }
+    assert!("spiral".parse::<Order>().is_err());
+}
+
+#[test]
+fn scanline_is_row_by_row() {
+    let indices = Order::Scanline.indices(5, 3);
+    assert_eq!(indices, (0..15).collect::<Vec<_>>());
+}
+
+#[test]
+fn hilbert_steps_to_neighbours() {
+    let indices = Order::Hilbert.indices(16, 16);
+    for pair in indices.windows(2) {
+        let (ax, ay) = xy(16, pair[0]);
+        let (bx, by) = xy(16, pair[1]);
+        assert_eq!(ax.abs_diff(bx) + ay.abs_diff(by), 1, "{:?}", pair);
+    }
+}
+
+#[test]
+fn zorder_fills_blocks() {
+    let indices = Order::ZOrder.indices(8, 8);
+    let first: Vec<_> = indices[..4].iter().map(|&i| xy(8, i)).collect();
+    assert_eq!(first, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+    // The first quarter is the top left 4 x 4 block
+    assert!(indices[..16]
+        .iter()
+        .map(|&i| xy(8, i))
+        .all(|(x, y)| x < 4 && y < 4));
+}
+
+#[test]
+fn dithering_spreads_the_first_pixels() {
+    let indices = Order::Dithered.indices(16, 16);
+    let first: Vec<_> = indices[..4].iter().map(|&i| xy(16, i)).collect();
+    assert_eq!(first, [(0, 0), (8, 0), (0, 8), (8, 8)]);
+}
+
+#[test]
+fn importance_sends_the_largest_changes_first() {
+    let mut scatter = scatter(4, 4, Order::Importance);
+    let mut buf = vec![0; 4 * 4 * 4];
+    scatter.pixels(buf.clone(), 4);
+
+    buf[5 * 4] = 10;
+    buf[9 * 4 + 1] = 200;
+    buf[2 * 4 + 2] = 50;
+    let pixels = scatter.pixels(buf, 4);
+    let sent: Vec<_> = pixels.iter().map(|p| (p.x, p.y)).collect();
+    assert_eq!(sent, [(1, 2), (2, 0), (1, 1)]);
+}
+
+#[test]
+fn orders_change_between_frames() {
+    let mut scatter = scatter(6, 4, Order::Scanline);
+    let buf = |v| vec![v; 6 * 4 * 4];
+    let first = scatter.pixels(buf(1), 4);
+    assert_eq!((first[0].x, first[0].y, first[1].x), (0, 0, 1));
+
+    scatter.set_order(Order::Hilbert);
+    let hilbert: Vec<_> = scatter
+        .pixels(buf(2), 4)
+        .iter()
+        .map(|p| (p.x, p.y))
+        .collect();
+    let expected: Vec<_> = Order::Hilbert
+        .indices(6, 4)
+        .into_iter()
+        .map(|i| (i as u16 % 6, i as u16 / 6))
+        .collect();
+    assert_eq!(hilbert, expected);
+}
@@ -0,0 +1,453 @@
+// Pixel sinks against local TCP stand-ins for francis and pixelflut servers.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use imager::francis::{FroxyConfig, Order, Protocol, SinkOptions};
+
+mod common;
+use common::{alpha, frame, region};
+
+/// Colours as received, r, g, b and a when alpha was sent
+type Canvas = HashMap<(u16, u16), Vec<u8>>;
+
+/// What a stand-in received: the canvas, the pixelflut commands other
+/// than `PX` and how many pixels were drawn
+type Received = (Canvas, Vec<String>, usize);
+
+/// A pixelflut server with a `width` x `height` canvas, serving `connections` connections.
+fn pixelflut_server(width: u16, height: u16, connections: usize) -> (String, JoinHandle<Received>) {
+    serve(connections, move |stream| {
+        let mut writer = stream.try_clone().unwrap();
+
+        let mut canvas = Canvas::new();
+        let mut commands = Vec::new();
+        let mut drawn = 0;
+        let (mut dx, mut dy) = (0, 0);
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            let parts: Vec<_> = line.split(' ').collect();
+            match parts[..] {
+                ["SIZE"] => writeln!(writer, "SIZE {} {}", width, height).unwrap(),
+                ["OFFSET", x, y] => (dx, dy) = (x.parse().unwrap(), y.parse().unwrap()),
+                ["PX", x, y, colour] => {
+                    let x: u16 = x.parse().unwrap();
+                    let y: u16 = y.parse().unwrap();
+                    let colour = (0..colour.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&colour[i..i + 2], 16).unwrap())
+                        .collect();
+                    canvas.insert((x + dx, y + dy), colour);
+                    drawn += 1;
+                    continue;
+                }
+                _ => panic!("unexpected command '{}'", line),
+            }
+            commands.push(line);
+        }
+        (canvas, commands, drawn)
+    })
+}
+
+/// Accepts `connections` connections, handling each on its own thread,
+/// and merges what they received.
+fn serve<F>(connections: usize, handle: F) -> (String, JoinHandle<Received>)
+where
+    F: Fn(TcpStream) -> Received + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handle = Arc::new(handle);
+
+    let server = thread::spawn(move || {
+        let threads: Vec<_> = (0..connections)
+            .map(|_| {
+                let (stream, _) = listener.accept().unwrap();
+                let handle = handle.clone();
+                thread::spawn(move || handle(stream))
+            })
+            .collect();
+
+        let mut received = (Canvas::new(), Vec::new(), 0);
+        for thread in threads {
+            let (canvas, commands, drawn) = thread.join().unwrap();
+            received.0.extend(canvas);
+            received.1.extend(commands);
+            received.2 += drawn;
+        }
+        received
+    });
+
+    (addr, server)
+}
+
+/// A francis server, reading 7 byte records from `connections` connections.
+fn francis_server(connections: usize) -> (String, JoinHandle<Received>) {
+    serve(connections, |mut stream| {
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len() % 7, 0, "partial record");
+
+        let mut canvas = Canvas::new();
+        for record in bytes.chunks_exact(7) {
+            let x = u16::from_be_bytes([record[0], record[1]]);
+            let y = u16::from_be_bytes([record[2], record[3]]);
+            canvas.insert((x, y), record[4..].to_vec());
+        }
+        let drawn = bytes.len() / 7;
+        (canvas, Vec::new(), drawn)
+    })
+}
+
+/// A binary pixelflut server reading `len` byte records starting with `prefix`,
+/// followed by little endian x and y and the colour, from a single connection.
+fn binary_pixelflut_server(len: usize, prefix: &'static [u8]) -> (String, JoinHandle<Received>) {
+    serve(1, move |mut stream| {
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len() % len, 0, "partial record");
+
+        let mut canvas = Canvas::new();
+        for record in bytes.chunks_exact(len) {
+            let (head, record) = record.split_at(prefix.len());
+            assert_eq!(head, prefix);
+            let x = u16::from_le_bytes([record[0], record[1]]);
+            let y = u16::from_le_bytes([record[2], record[3]]);
+            canvas.insert((x, y), record[4..].to_vec());
+        }
+        let drawn = bytes.len() / len;
+        (canvas, Vec::new(), drawn)
+    })
+}
+
+/// Checks every pixel of the region. `alpha_sent` is `None` when no alpha was sent,
+/// otherwise the alpha every pixel was sent with, or `Some(None)` for the frame's own.
+fn assert_drawn_with(canvas: &Canvas, region: FroxyConfig, alpha_sent: Option<Option<u8>>) {
+    for y in 0..region.height {
+        for x in 0..region.width {
+            let mut expected = vec![200, y as u8 * 20, x as u8 * 10];
+            if let Some(a) = alpha_sent {
+                expected.push(a.unwrap_or_else(|| alpha(x, y)));
+            }
+            assert_eq!(
+                canvas.get(&(x + region.x, y + region.y)),
+                Some(&expected),
+                "pixel {}, {}",
+                x,
+                y
+            );
+        }
+    }
+}
+
+fn assert_drawn(canvas: &Canvas, region: FroxyConfig) {
+    assert_drawn_with(canvas, region, None);
+}
+
+const ALPHA: SinkOptions = SinkOptions {
+    offset: false,
+    alpha: true,
+    connections: 1,
+    batch: 5000,
+    diff: true,
+    threshold: 0,
+    refresh: None,
+    order: Order::Random,
+};
+
+#[tokio::test]
+async fn pixelflut_discovers_the_canvas_size() {
+    let (addr, server) = pixelflut_server(5, 3, 2);
+
+    let mut sink = Protocol::Text
+        .connect(&addr, None, SinkOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((sink.width(), sink.height()), (5, 3));
+    sink.write(frame(5, 3), 4).await.unwrap();
+    drop(sink);
+
+    let (canvas, commands, _) = server.join().unwrap();
+    assert_eq!(commands, ["SIZE"]);
+    assert_eq!(canvas.len(), 15);
+    assert_drawn(&canvas, region(0, 0, 5, 3));
+}
+
+#[tokio::test]
+async fn pixelflut_draws_in_a_region() {
+    let (addr, server) = pixelflut_server(100, 100, 1);
+
+    let mut sink = Protocol::Text
+        .connect(&addr, Some(region(10, 20, 4, 2)), SinkOptions::default())
+        .await
+        .unwrap();
+    sink.write(frame(4, 2), 4).await.unwrap();
+    drop(sink);
+
+    let (canvas, commands, _) = server.join().unwrap();
+    assert!(commands.is_empty());
+    assert_drawn(&canvas, region(10, 20, 4, 2));
+}
+
+#[tokio::test]
+async fn pixelflut_offset_and_unchanged_pixels() {
+    let (addr, server) = pixelflut_server(100, 100, 1);
+
+    let mut sink = Protocol::Text
+        .connect(
+            &addr,
+            Some(region(7, 3, 4, 4)),
+            SinkOptions {
+                offset: true,
+                ..SinkOptions::default()
+            },
+        )
+        .await
+        .unwrap();
+    sink.write(frame(4, 4), 4).await.unwrap();
+    // Nothing changed, nothing should be sent
+    sink.write(frame(4, 4), 4).await.unwrap();
+    drop(sink);
+
+    let (canvas, commands, drawn) = server.join().unwrap();
+    assert_eq!(commands, ["OFFSET 7 3"]);
+    assert_eq!(drawn, 16);
+    assert_drawn(&canvas, region(7, 3, 4, 4));
+}
+
+#[tokio::test]
+async fn binary_draws_in_a_region() {
+    let (addr, server) = francis_server(1);
+
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(64, 8, 6, 5)), SinkOptions::default())
+        .await
+        .unwrap();
+    sink.write(frame(6, 5), 4).await.unwrap();
+    drop(sink);
+
+    let (canvas, _, _) = server.join().unwrap();
+    assert_drawn(&canvas, region(64, 8, 6, 5));
+}
+
+#[tokio::test]
+async fn pixelflut_text_with_alpha() {
+    let (addr, server) = pixelflut_server(100, 100, 1);
+
+    let mut sink = Protocol::Text
+        .connect(&addr, Some(region(1, 2, 3, 3)), ALPHA)
+        .await
+        .unwrap();
+    sink.write(frame(3, 3), 4).await.unwrap();
+    drop(sink);
+
+    let (canvas, _, _) = server.join().unwrap();
+    assert_drawn_with(&canvas, region(1, 2, 3, 3), Some(None));
+}
+
+#[tokio::test]
+async fn pb_is_opaque_without_alpha() {
+    let (addr, server) = binary_pixelflut_server(10, b"PB");
+
+    let mut sink = Protocol::Pb
+        .connect(&addr, Some(region(300, 2, 5, 4)), SinkOptions::default())
+        .await
+        .unwrap();
+    sink.write(frame(5, 4), 4).await.unwrap();
+    drop(sink);
+
+    assert_drawn_with(
+        &server.join().unwrap().0,
+        region(300, 2, 5, 4),
+        Some(Some(255)),
+    );
+}
+
+#[tokio::test]
+async fn pb_with_alpha() {
+    let (addr, server) = binary_pixelflut_server(10, b"PB");
+
+    let mut sink = Protocol::Pb
+        .connect(&addr, Some(region(0, 0, 5, 4)), ALPHA)
+        .await
+        .unwrap();
+    sink.write(frame(5, 4), 4).await.unwrap();
+    drop(sink);
+
+    assert_drawn_with(&server.join().unwrap().0, region(0, 0, 5, 4), Some(None));
+}
+
+#[tokio::test]
+async fn packed_with_and_without_alpha() {
+    let (addr, server) = binary_pixelflut_server(7, b"");
+    let mut sink = Protocol::Packed
+        .connect(&addr, Some(region(4, 9, 3, 2)), SinkOptions::default())
+        .await
+        .unwrap();
+    sink.write(frame(3, 2), 4).await.unwrap();
+    drop(sink);
+    assert_drawn(&server.join().unwrap().0, region(4, 9, 3, 2));
+
+    let (addr, server) = binary_pixelflut_server(8, b"");
+    let mut sink = Protocol::Packed
+        .connect(&addr, Some(region(4, 9, 3, 2)), ALPHA)
+        .await
+        .unwrap();
+    sink.write(frame(3, 2), 4).await.unwrap();
+    drop(sink);
+    assert_drawn_with(&server.join().unwrap().0, region(4, 9, 3, 2), Some(None));
+}
+
+#[tokio::test]
+async fn binary_needs_a_region_and_has_no_alpha() {
+    let options = SinkOptions::default();
+    assert!(Protocol::Binary
+        .connect("127.0.0.1:1", None, options)
+        .await
+        .is_err());
+
+    let region = Some(region(0, 0, 1, 1));
+    assert!(Protocol::Binary
+        .connect("127.0.0.1:1", region, ALPHA)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn regions_past_the_largest_coordinate_are_rejected() {
+    let options = SinkOptions::default();
+    for protocol in [Protocol::Binary, Protocol::Text, Protocol::Pb, Protocol::Packed] {
+        for region in [region(65500, 0, 100, 1), region(0, 65535, 1, 2)] {
+            let error = protocol
+                .connect("127.0.0.1:1", Some(region), options)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}
+
+#[tokio::test]
+async fn tiles_are_drawn_over_their_own_connections() {
+    let options = SinkOptions {
+        connections: 3,
+        ..SinkOptions::default()
+    };
+
+    let (addr, server) = francis_server(3);
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(10, 5, 6, 7)), options)
+        .await
+        .unwrap();
+    assert_eq!((sink.width(), sink.height()), (6, 7));
+    assert_eq!(sink.write(frame(6, 7), 4).await.unwrap(), 42);
+    drop(sink);
+    let (canvas, _, drawn) = server.join().unwrap();
+    assert_eq!(drawn, 42);
+    assert_drawn(&canvas, region(10, 5, 6, 7));
+
+    // More connections than rows leaves a row per connection
+    let (addr, server) = pixelflut_server(100, 100, 2);
+    let options = SinkOptions {
+        offset: true,
+        connections: 5,
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Text
+        .connect(&addr, Some(region(3, 4, 5, 2)), options)
+        .await
+        .unwrap();
+    sink.write(frame(5, 2), 4).await.unwrap();
+    drop(sink);
+    let (canvas, mut commands, _) = server.join().unwrap();
+    commands.sort();
+    assert_eq!(commands, ["OFFSET 3 4", "OFFSET 3 5"]);
+    assert_drawn(&canvas, region(3, 4, 5, 2));
+}
+
+#[tokio::test]
+async fn binary_writes_exactly_the_pending_records() {
+    let (addr, server) = francis_server(1);
+
+    let options = SinkOptions {
+        batch: 4,
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(2, 3, 5, 3)), options)
+        .await
+        .unwrap();
+    assert_eq!(sink.write(frame(5, 3), 4).await.unwrap(), 15);
+    assert_eq!(sink.write(frame(5, 3), 4).await.unwrap(), 0);
+    drop(sink);
+
+    let (canvas, _, drawn) = server.join().unwrap();
+    assert_eq!(drawn, 15);
+    assert_eq!(canvas.len(), 15);
+    assert_drawn(&canvas, region(2, 3, 5, 3));
+}
+
+#[tokio::test]
+async fn changes_within_the_threshold_are_not_sent() {
+    let (addr, server) = pixelflut_server(100, 100, 1);
+
+    let options = SinkOptions {
+        threshold: 4,
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Text
+        .connect(&addr, Some(region(0, 0, 4, 4)), options)
+        .await
+        .unwrap();
+    let mut buf = frame(4, 4);
+    sink.write(buf.clone(), 4).await.unwrap();
+
+    // Drifting a pixel a little at a time sends it once the drift adds up
+    buf[2] += 3;
+    assert_eq!(sink.write(buf.clone(), 4).await.unwrap(), 0);
+    buf[2] += 3;
+    assert_eq!(sink.write(buf.clone(), 4).await.unwrap(), 1);
+    assert_eq!(sink.write(buf, 4).await.unwrap(), 0);
+    drop(sink);
+
+    let (canvas, _, _) = server.join().unwrap();
+    assert_eq!(canvas[&(0, 0)][0], 206);
+}
+
+#[tokio::test]
+async fn refreshes_and_disabled_diffs_resend_everything() {
+    let (addr, server) = francis_server(2);
+
+    let refresh = SinkOptions {
+        refresh: Some(0),
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(0, 0, 3, 3)), refresh)
+        .await
+        .unwrap();
+    sink.write(frame(3, 3), 4).await.unwrap();
+    assert_eq!(sink.write(frame(3, 3), 4).await.unwrap(), 9);
+    drop(sink);
+
+    let no_diff = SinkOptions {
+        diff: false,
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(0, 0, 3, 3)), no_diff)
+        .await
+        .unwrap();
+    sink.write(frame(3, 3), 4).await.unwrap();
+    assert_eq!(sink.write(frame(3, 3), 4).await.unwrap(), 9);
+    drop(sink);
+
+    let (_, _, drawn) = server.join().unwrap();
+    assert_eq!(drawn, 36);
+}
@@ -0,0 +1,236 @@
+// Building the validated program from shader toy render passes.
+
+use imager::shadertoy::{
+    ChannelSource, Filter, PassOutput, Program, RenderPass, SamplerDesc, Wrap,
+};
+use serde_json::{json, Value};
+
+fn passes(json: Value) -> Vec<RenderPass> {
+    serde_json::from_value(json).unwrap()
+}
+
+fn pass(kind: &str, name: &str, output: Option<u64>, inputs: Value) -> Value {
+    let outputs = match output {
+        Some(id) => json!([{ "id": id, "channel": 0 }]),
+        None => json!([]),
+    };
+    json!({ "type": kind, "name": name, "code": name, "inputs": inputs, "outputs": outputs })
+}
+
+fn input(ctype: &str, id: u64, channel: u64) -> Value {
+    json!({ "ctype": ctype, "id": id, "channel": channel, "src": format!("/media/{}.png", id) })
+}
+
+fn sampled(filter: &str, wrap: &str, srgb: &str) -> Value {
+    let mut input = input("texture", 1, 0);
+    input["sampler"] = json!({ "filter": filter, "wrap": wrap, "vflip": "true", "srgb": srgb });
+    input
+}
+
+#[test]
+fn buffers_render_in_order_before_the_image() {
+    let program = Program::new(
+        "order",
+        &passes(json!([
+            pass("image", "Image", None, json!([])),
+            pass("common", "Common", None, json!([])),
+            pass("buffer", "Buffer A", Some(257), json!([])),
+            pass("buffer", "Buffer B", Some(258), json!([])),
+            pass("common", "More", None, json!([])),
+        ])),
+    )
+    .unwrap();
+
+    let order: Vec<_> = program.passes.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(order, ["Buffer A", "Buffer B", "Image"]);
+
+    let outputs: Vec<_> = program.passes.iter().map(|p| p.output).collect();
+    assert_eq!(
+        outputs,
+        [
+            PassOutput::Buffer(257),
+            PassOutput::Buffer(258),
+            PassOutput::Screen
+        ]
+    );
+    assert_eq!(program.common, "CommonMore");
+    assert!(program.skipped.is_empty());
+}
+
+#[test]
+fn buffer_channels_are_wired_by_id() {
+    let program = Program::new(
+        "wiring",
+        &passes(json!([
+            pass(
+                "image",
+                "Image",
+                None,
+                json!([input("buffer", 258, 1), input("buffer", 257, 0)])
+            ),
+            pass(
+                "buffer",
+                "Buffer A",
+                Some(257),
+                json!([input("buffer", 257, 2)])
+            ),
+            pass(
+                "buffer",
+                "Buffer B",
+                Some(258),
+                json!([input("buffer", 257, 0)])
+            ),
+        ])),
+    )
+    .unwrap();
+
+    let channels = |name: &str| -> Vec<(u64, ChannelSource)> {
+        let pass = program.passes.iter().find(|p| p.name == name).unwrap();
+        pass.channels
+            .iter()
+            .map(|c| (c.index, c.source.clone()))
+            .collect()
+    };
+    assert_eq!(
+        channels("Image"),
+        [
+            (1, ChannelSource::Buffer(258)),
+            (0, ChannelSource::Buffer(257))
+        ]
+    );
+    // A buffer may read its own output from the previous frame
+    assert_eq!(channels("Buffer A"), [(2, ChannelSource::Buffer(257))]);
+    assert_eq!(channels("Buffer B"), [(0, ChannelSource::Buffer(257))]);
+}
+
+#[test]
+fn unsupported_inputs_are_black() {
+    let program = Program::new(
+        "unsupported",
+        &passes(json!([
+            pass(
+                "image",
+                "Image",
+                None,
+                json!([
+                    input("music", 1, 0),
+                    input("webcam", 2, 1),
+                    input("buffer", 300, 2),
+                    input("hologram", 3, 3),
+                    input("texture", 4, 3),
+                    input("texture", 5, 4),
+                ])
+            ),
+            pass("sound", "Sound", None, json!([])),
+        ])),
+    )
+    .unwrap();
+
+    let image = &program.passes[0];
+    let sources: Vec<_> = image.channels.iter().map(|c| &c.source).collect();
+    assert_eq!(sources, [&ChannelSource::Black; 4]);
+
+    let skipped: Vec<_> = program.skipped.iter().map(|s| s.to_string()).collect();
+    assert_eq!(
+        skipped,
+        [
+            "'Image': channel 0 (music)",
+            "'Image': channel 1 (webcam)",
+            "'Image': channel 2 (buffer 300 is not rendered by any pass)",
+            "'Image': channel 3 (hologram)",
+            "'Image': channel 3 (texture, channel used twice)",
+            "'Image': channel 4 (texture, channel out of range)",
+            "'Sound': sound pass",
+        ]
+    );
+    assert_eq!(program.passes.len(), 1);
+}
+
+#[test]
+fn supported_inputs_keep_their_source() {
+    let program = Program::new(
+        "supported",
+        &passes(json!([pass(
+            "image",
+            "Image",
+            None,
+            json!([
+                input("texture", 1, 0),
+                input("cubemap", 2, 1),
+                input("keyboard", 3, 2)
+            ])
+        )])),
+    )
+    .unwrap();
+
+    let sources: Vec<_> = program.passes[0]
+        .channels
+        .iter()
+        .map(|c| c.source.clone())
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            ChannelSource::Texture("/media/1.png".into()),
+            ChannelSource::Cubemap("/media/2.png".into()),
+            ChannelSource::Keyboard,
+        ]
+    );
+}
+
+#[test]
+fn samplers_map_to_filter_wrap_and_srgb() {
+    let cases = [
+        (
+            sampled("nearest", "clamp", "false"),
+            Filter::Nearest,
+            Wrap::Clamp,
+            false,
+        ),
+        (
+            sampled("linear", "repeat", "true"),
+            Filter::Linear,
+            Wrap::Repeat,
+            true,
+        ),
+        // Mipmaps are sampled linearly
+        (
+            sampled("mipmap", "clamp", "false"),
+            Filter::Linear,
+            Wrap::Clamp,
+            false,
+        ),
+        // Unknown modes fall back to the defaults
+        (
+            sampled("cubic", "mirror", "false"),
+            Filter::Linear,
+            Wrap::Repeat,
+            false,
+        ),
+        (input("texture", 1, 0), Filter::Linear, Wrap::Repeat, false),
+    ];
+
+    for (input, filter, wrap, srgb) in cases {
+        let program = Program::new(
+            "sampler",
+            &passes(json!([pass("image", "Image", None, json!([input]))])),
+        )
+        .unwrap();
+        assert_eq!(
+            program.passes[0].channels[0].sampler,
+            SamplerDesc { filter, wrap, srgb }
+        );
+    }
+}
+
+#[test]
+fn broken_programs_are_errors() {
+    let no_image = passes(json!([pass("buffer", "Buffer A", Some(257), json!([]))]));
+    assert!(Program::new("no image", &no_image).is_err());
+
+    let no_output = passes(json!([
+        pass("image", "Image", None, json!([])),
+        pass("buffer", "Buffer A", None, json!([])),
+    ]));
+    assert!(Program::new("no output", &no_output).is_err());
+}
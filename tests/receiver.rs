@@ -0,0 +1,197 @@
+// The local receiver against the pixel sinks that draw on the wall.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use imager::{
+    francis::{FroxyConfig, Order, Protocol, SinkOptions},
+    receiver::{dump_png, Canvas, Receiver, SharedCanvas},
+};
+
+mod common;
+use common::{alpha, frame, region};
+
+/// Starts a receiver on a free port, returns its address and canvas.
+async fn receiver(width: u32, height: u32, protocol: Option<Protocol>) -> (String, SharedCanvas) {
+    let canvas = Canvas::shared(width, height);
+    let receiver = Receiver::bind("127.0.0.1:0", canvas.clone(), protocol, true)
+        .await
+        .unwrap();
+    let addr = receiver.local_addr().unwrap().to_string();
+    async_std::task::spawn(receiver.run());
+    (addr, canvas)
+}
+
+/// Waits until `pixels` pixels were drawn, the receiver draws in the background.
+async fn wait_for(canvas: &SharedCanvas, pixels: u64) {
+    for _ in 0..500 {
+        if canvas.lock().unwrap().generation() >= pixels {
+            return;
+        }
+        async_std::task::sleep(Duration::from_millis(10)).await;
+    }
+    panic!(
+        "only {} of {} pixels were drawn",
+        canvas.lock().unwrap().generation(),
+        pixels
+    );
+}
+
+/// Checks every pixel of the canvas, the region drawn over black with or without alpha.
+fn assert_drawn(canvas: &SharedCanvas, region: FroxyConfig, blended: bool) {
+    let canvas = canvas.lock().unwrap();
+    for y in 0..canvas.height() {
+        for x in 0..canvas.width() {
+            let inside = (region.x as u32..(region.x + region.width) as u32).contains(&x)
+                && (region.y as u32..(region.y + region.height) as u32).contains(&y);
+            let expected = if inside {
+                let (rx, ry) = ((x - region.x as u32) as u16, (y - region.y as u32) as u16);
+                let a = if blended { alpha(rx, ry) as u32 } else { 255 };
+                let c = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+                [c(200), c(ry as u8 * 20), c(rx as u8 * 10), 255]
+            } else {
+                [0, 0, 0, 255]
+            };
+            assert_eq!(canvas.get(x, y), Some(expected), "pixel {}, {}", x, y);
+        }
+    }
+}
+
+#[tokio::test]
+async fn francis_connections_are_detected() {
+    let (addr, canvas) = receiver(16, 12, None).await;
+
+    let mut sink = Protocol::Binary
+        .connect(&addr, Some(region(5, 4, 6, 5)), SinkOptions::default())
+        .await
+        .unwrap();
+    sink.write(frame(6, 5), 4).await.unwrap();
+    drop(sink);
+
+    wait_for(&canvas, 30).await;
+    assert_drawn(&canvas, region(5, 4, 6, 5), false);
+}
+
+#[tokio::test]
+async fn pixelflut_text_with_offset_and_alpha() {
+    let (addr, canvas) = receiver(10, 8, None).await;
+
+    let options = SinkOptions {
+        offset: true,
+        alpha: true,
+        connections: 2,
+        order: Order::Hilbert,
+        ..SinkOptions::default()
+    };
+    let mut sink = Protocol::Text
+        .connect(&addr, Some(region(3, 2, 5, 4)), options)
+        .await
+        .unwrap();
+    sink.write(frame(5, 4), 4).await.unwrap();
+    drop(sink);
+
+    wait_for(&canvas, 20).await;
+    assert_drawn(&canvas, region(3, 2, 5, 4), true);
+}
+
+#[tokio::test]
+async fn pixelflut_discovers_the_canvas_size() {
+    let (addr, canvas) = receiver(7, 3, None).await;
+
+    let mut sink = Protocol::Text
+        .connect(&addr, None, SinkOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((sink.width(), sink.height()), (7, 3));
+    sink.write(frame(7, 3), 4).await.unwrap();
+    drop(sink);
+
+    wait_for(&canvas, 21).await;
+    assert_drawn(&canvas, region(0, 0, 7, 3), false);
+}
+
+#[tokio::test]
+async fn binary_pixelflut_extensions() {
+    for protocol in [Protocol::Pb, Protocol::Packed] {
+        let (addr, canvas) = receiver(9, 9, Some(protocol)).await;
+
+        let options = SinkOptions {
+            alpha: true,
+            ..SinkOptions::default()
+        };
+        let mut sink = protocol
+            .connect(&addr, Some(region(1, 2, 4, 6)), options)
+            .await
+            .unwrap();
+        sink.write(frame(4, 6), 4).await.unwrap();
+        drop(sink);
+
+        wait_for(&canvas, 24).await;
+        assert_drawn(&canvas, region(1, 2, 4, 6), true);
+    }
+}
+
+#[tokio::test]
+async fn text_commands_read_back_and_fail_on_nonsense() {
+    let (addr, _canvas) = receiver(4, 4, None).await;
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"OFFSET 1 1\nPX 1 2 0a0b0c\nPX 1 2\nPX 9 9\nHELP\n")
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    // Pixels outside the canvas are not answered
+    assert_eq!(line, "PX 1 2 0a0b0c\n");
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("HELP"));
+
+    // The last one moves the coordinate past the largest one
+    for nonsense in ["PX 1 1 red", "FILL 0 0", "PX 4294967295 0 ffffff"] {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .write_all(format!("OFFSET 1 1\n{}\n", nonsense).as_bytes())
+            .unwrap();
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+        assert!(
+            rest.is_empty(),
+            "'{}' should close the connection",
+            nonsense
+        );
+    }
+}
+
+#[tokio::test]
+async fn canvas_is_dumped_to_png() {
+    let dir = std::env::temp_dir().join(format!("receiver-png-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("canvas.png");
+
+    let canvas = Canvas::shared(3, 2);
+    canvas.lock().unwrap().set(2, 1, [10, 20, 30, 255]);
+    let dumping = async_std::task::spawn(dump_png(
+        canvas.clone(),
+        path.clone(),
+        Duration::from_millis(10),
+    ));
+
+    for _ in 0..500 {
+        if path.exists() {
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(10)).await;
+    }
+    let image = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.get_pixel(2, 1).0, [10, 20, 30, 255]);
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+    dumping.cancel().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,157 @@
+// The playlist search grammar, and resolving searches against a local stand-in for the API.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use imager::shadertoy::{Client, ClientOptions, SearchFilter, SearchQuery, SearchSortOrder};
+
+fn parse(s: &str) -> SearchQuery {
+    s.parse().unwrap_or_else(|e| panic!("'{}': {}", s, e))
+}
+
+#[test]
+fn defaults() {
+    let query = parse("");
+    assert_eq!(query.string, "");
+    assert_eq!(query.sort_order, SearchSortOrder::Popular);
+    assert!(query.filters.is_empty());
+    assert_eq!(query.limit, None);
+}
+
+#[test]
+fn clauses() {
+    let query = parse("top 20 popular with filter multipass");
+    assert_eq!(query.limit, Some(20));
+    assert_eq!(query.sort_order, SearchSortOrder::Popular);
+    assert_eq!(query.filters, [SearchFilter::MultiPass]);
+
+    let query = parse("Top 5 NEWEST matching Fire and Ice");
+    assert_eq!(query.limit, Some(5));
+    assert_eq!(query.sort_order, SearchSortOrder::Newest);
+    assert_eq!(query.string, "fire and ice");
+
+    // Words end at 'with', filters are separated by commas or spaces and `filter` is optional
+    let query = parse("loved matching rain with vr, webcam with filter soundinput");
+    assert_eq!(query.sort_order, SearchSortOrder::Love);
+    assert_eq!(query.string, "rain");
+    assert_eq!(
+        query.filters,
+        [
+            SearchFilter::Vr,
+            SearchFilter::Webcam,
+            SearchFilter::SoundInput
+        ]
+    );
+
+    for (word, order) in [
+        ("name", SearchSortOrder::Name),
+        ("love", SearchSortOrder::Love),
+        ("new", SearchSortOrder::Newest),
+        ("hot", SearchSortOrder::Hot),
+    ] {
+        assert_eq!(parse(word).sort_order, order);
+    }
+}
+
+#[test]
+fn errors() {
+    for s in [
+        "top",
+        "top many",
+        "top -1",
+        "with",
+        "with filter",
+        "with cats",
+        "with multipass with",
+        "with multipass with filter",
+        "matching",
+        "matching with vr",
+        "popular sideways",
+    ] {
+        assert!(s.parse::<SearchQuery>().is_err(), "'{}' parsed", s);
+    }
+}
+
+/// Serves the search API, answering every request with `ids` and counting the requests.
+fn api(ids: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let counted = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            counted.fetch_add(1, Ordering::SeqCst);
+
+            let body = format!(r#"{{"Shaders": 3, "Results": {}}}"#, ids);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+fn client(base_url: &str, cache_root: &Path, offline: bool) -> Client {
+    Client::with_options(
+        "key",
+        ClientOptions {
+            base_url: base_url.to_string(),
+            cache_root: cache_root.to_path_buf(),
+            offline,
+            retries: 0,
+            ..ClientOptions::default()
+        },
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn resolved_searches_are_cached() {
+    let (url, requests) = api(r#"["a", "b", "c"]"#);
+    let cache = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("search_cache");
+    let _ = std::fs::remove_dir_all(&cache);
+
+    let query = parse("top 2 hot matching rain");
+    let online = client(&url, &cache, false);
+
+    // Uncached searches always ask the API
+    assert_eq!(query.resolve(&online, false).await.unwrap(), ["a", "b"]);
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    assert_eq!(query.resolve(&online, true).await.unwrap(), ["a", "b"]);
+    assert_eq!(query.resolve(&online, true).await.unwrap(), ["a", "b"]);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // The results are tracked like every other cached resource
+    let entries = online.cache.entries();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].0.starts_with("search/"));
+    assert!(entries[0].1.url.contains("/query/rain?sort=hot"));
+    assert!(!entries[0].1.url.contains("key"));
+
+    // A new client finds them in the manifest, even offline
+    let offline = client(&url, &cache, true);
+    assert_eq!(query.resolve(&offline, true).await.unwrap(), ["a", "b"]);
+    assert!(parse("top 2 newest").resolve(&offline, true).await.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
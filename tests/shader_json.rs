@@ -0,0 +1,142 @@
+// Lenient and strict parsing of shader toy json, on the downloaded shaders.
+
+use std::path::Path;
+
+use imager::shadertoy::{ChannelType, ErrorKind, FilterMode, ParseMode, Shader};
+use serde_json::{json, Value};
+
+fn download(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("downloads")
+        .join(name);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn parse(json: &Value, mode: ParseMode) -> Result<Shader, String> {
+    Shader::from_json(&json.to_string(), mode).map_err(|e| match e.kind() {
+        ErrorKind::Validation(problems) => problems.clone(),
+        other => panic!("expected a validation error, got {}", other),
+    })
+}
+
+/// The first input of the first pass, where diffuse reads its texture.
+fn input(json: &mut Value) -> &mut Value {
+    &mut json["renderpass"][0]["inputs"][0]
+}
+
+#[test]
+fn downloaded_shaders_are_strict() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("downloads");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let json = std::fs::read_to_string(&path).unwrap();
+        if let Err(e) = Shader::from_json(&json, ParseMode::Strict) {
+            panic!("{}: {}", path.display(), e);
+        }
+    }
+}
+
+#[test]
+fn string_bools() {
+    let mut json = download("diffuse.json");
+    input(&mut json)["sampler"]["vflip"] = json!("true");
+    let shader = parse(&json, ParseMode::Strict).unwrap();
+    assert!(shader.renderpass[0].inputs[0].sampler.vflip);
+
+    input(&mut json)["sampler"]["vflip"] = json!(false);
+    input(&mut json)["sampler"]["srgb"] = json!(true);
+    let shader = parse(&json, ParseMode::Lenient).unwrap();
+    let sampler = &shader.renderpass[0].inputs[0].sampler;
+    assert!(!sampler.vflip);
+    assert!(sampler.srgb);
+
+    let problems = parse(&json, ParseMode::Strict).unwrap_err();
+    assert!(problems.contains("vflip: expected a string, found a boolean"));
+    assert!(problems.contains("srgb: expected a string, found a boolean"));
+
+    // Neither mode takes other strings
+    input(&mut json)["sampler"]["vflip"] = json!("yes");
+    assert!(Shader::from_json(&json.to_string(), ParseMode::Lenient).is_err());
+}
+
+#[test]
+fn string_bools_are_written_as_strings() {
+    let shader = parse(&download("diffuse.json"), ParseMode::Strict).unwrap();
+    let written = serde_json::to_value(&shader).unwrap();
+    assert_eq!(
+        written["renderpass"][0]["inputs"][0]["sampler"]["srgb"],
+        json!("false")
+    );
+}
+
+#[test]
+fn unknown_enum_values() {
+    let mut json = download("diffuse.json");
+    input(&mut json)["ctype"] = json!("hologram");
+    input(&mut json)["sampler"]["filter"] = json!("anisotropic");
+    json["renderpass"][0]["type"] = json!("volume");
+
+    let shader = parse(&json, ParseMode::Lenient).unwrap();
+    let pass = &shader.renderpass[0];
+    assert_eq!(
+        pass.inputs[0].ctype,
+        ChannelType::Unknown("hologram".into())
+    );
+    assert_eq!(
+        pass.inputs[0].sampler.filter,
+        FilterMode::Unknown("anisotropic".into())
+    );
+    assert!(pass.pass_type.is_unknown());
+
+    let problems = parse(&json, ParseMode::Strict).unwrap_err();
+    assert!(problems.contains("renderpass[0]: unknown type 'volume'"));
+    assert!(problems.contains("renderpass[0].inputs[0]: unknown ctype 'hologram'"));
+    assert!(problems.contains("renderpass[0].inputs[0]: unknown filter 'anisotropic'"));
+}
+
+#[test]
+fn extra_fields() {
+    let mut json = download("diffuse.json");
+    json["info"]["rating"] = json!(5);
+    input(&mut json)["sampler"]["anisotropy"] = json!(16);
+
+    assert!(parse(&json, ParseMode::Lenient).is_ok());
+
+    let problems = parse(&json, ParseMode::Strict).unwrap_err();
+    assert!(problems.contains(".info.rating: unknown field"));
+    assert!(problems.contains(".renderpass[0].inputs[0].sampler.anisotropy: unknown field"));
+}
+
+#[test]
+fn missing_fields() {
+    let mut json = download("diffuse.json");
+    json["info"].as_object_mut().unwrap().remove("likes");
+    input(&mut json)["sampler"]
+        .as_object_mut()
+        .unwrap()
+        .remove("wrap");
+    json["renderpass"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("outputs");
+
+    let shader = parse(&json, ParseMode::Lenient).unwrap();
+    assert_eq!(shader.info.likes, 0);
+    assert_eq!(
+        shader.renderpass[0].inputs[0].sampler.wrap.as_str(),
+        "repeat"
+    );
+    assert!(shader.renderpass[0].outputs.is_empty());
+
+    let problems = parse(&json, ParseMode::Strict).unwrap_err();
+    assert!(problems.contains(".info.likes: missing field"));
+    assert!(problems.contains(".renderpass[0].inputs[0].sampler.wrap: missing field"));
+    assert!(problems.contains(".renderpass[0].outputs: missing field"));
+
+    // The code is required in both modes
+    json["renderpass"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("code");
+    assert!(Shader::from_json(&json.to_string(), ParseMode::Lenient).is_err());
+}